@@ -1,21 +1,391 @@
-use object::{self, Architecture, Object, ObjectSection, ReadCache, ReadRef, SectionKind};
-use std::fs;
+use object::{
+    self, elf, macho, pe,
+    pod::{from_bytes, slice_from_bytes},
+    read::{
+        coff::{CoffBigFile, CoffFile},
+        elf::{ElfFile32, ElfFile64},
+        macho::{FatArch, MachOFatFile32, MachOFatFile64, MachOFile32, MachOFile64},
+        pe::{ImageNtHeaders, ImageOptionalHeader, PeFile32, PeFile64},
+    },
+    Architecture, BinaryFormat, Endianness, FileFlags, FileKind, LittleEndian as LE, Object,
+    ObjectKind, ObjectSection, ObjectSegment, ObjectSymbol, ReadCache, ReadRef, SectionFlags,
+    SectionKind, SegmentFlags, SymbolKind, U32,
+};
+use std::io::{Read, Seek, SeekFrom};
 
 use crate::{
     check,
     cli::OutputMode,
     error::{AppError, R},
+    warnings, E,
 };
 
-type Params = (Vec<(u64, u64)>, u32);
+/// Seekable input the binary is read from, a file or a decompressed buffer.
+pub trait Source: Read + Seek {}
+
+impl<T: Read + Seek> Source for T {}
+
+pub struct Segment {
+    pub offset: u64,
+    pub size: u64,
+    pub address: u64,
+    /// Bitness to decode with instead of the file one, set per section.
+    pub bitness: Option<u32>,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+    pub address: u64,
+}
+
+impl Segment {
+    /// The part of the segment starting at file offset `from` or later, if any.
+    pub fn starting_at(&self, from: u64) -> Option<Segment> {
+        let skip = from.saturating_sub(self.offset);
+        (skip < self.size).then(|| Segment {
+            offset: self.offset + skip,
+            size: self.size - skip,
+            address: self.address + skip,
+            bitness: self.bitness,
+        })
+    }
+}
+
+impl Symbol {
+    pub fn segment(&self) -> Segment {
+        Segment {
+            offset: self.offset,
+            size: self.size,
+            address: self.address,
+            bitness: None,
+        }
+    }
+
+    pub fn end(&self) -> u64 {
+        self.address + self.size
+    }
+}
+
+/// Header parsing parameters.
+#[derive(Clone, Copy)]
+pub struct Options<'a> {
+    /// Format to parse as instead of detecting it.
+    pub format: Option<BinaryFormat>,
+    /// Read function symbols.
+    pub symbols: bool,
+    /// Required architecture.
+    pub arch: Option<Architecture>,
+    /// Skip writable or non-executable sections.
+    pub exec_only: bool,
+    /// Section kinds to decode.
+    pub kinds: &'a [SectionKind],
+    /// Bitness of named sections, overriding the file one.
+    pub section_bitness: &'a [(String, u32)],
+}
+
+pub struct Binary {
+    pub sections: Vec<Segment>,
+    pub symbols: Vec<Symbol>,
+    pub bitness: u32,
+}
+
+/// `rwx`-style summary of section flags, `?` when the format does not store them.
+fn permissions(flags: SectionFlags) -> String {
+    let (r, w, x) = match flags {
+        SectionFlags::Elf { sh_flags } => (
+            sh_flags & elf::SHF_ALLOC as u64 != 0,
+            sh_flags & elf::SHF_WRITE as u64 != 0,
+            sh_flags & elf::SHF_EXECINSTR as u64 != 0,
+        ),
+        SectionFlags::Coff { characteristics } => (
+            characteristics & pe::IMAGE_SCN_MEM_READ != 0,
+            characteristics & pe::IMAGE_SCN_MEM_WRITE != 0,
+            characteristics & pe::IMAGE_SCN_MEM_EXECUTE != 0,
+        ),
+        _ => return "???".into(),
+    };
+
+    [(r, 'r'), (w, 'w'), (x, 'x')]
+        .iter()
+        .map(|&(set, c)| match set {
+            true => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Sections of relocatable objects all start at zero address,
+/// so file offsets are used instead to keep them apart.
+fn section_address<'a>(section: &impl ObjectSection<'a>, relocatable: bool) -> Option<u64> {
+    match relocatable {
+        true => Some(section.file_range()?.0),
+        _ => Some(section.address()),
+    }
+}
+
+fn map_segment<'a>(
+    section: &impl ObjectSection<'a>,
+    relocatable: bool,
+    options: Options,
+    output_mode: OutputMode,
+) -> Option<Segment> {
+    if !options.kinds.contains(&section.kind()) || section.size() == 0 {
+        return None;
+    }
+
+    let (offset, size) = section.file_range()?;
+    let address = section_address(section, relocatable)?;
+
+    let name = section.name().unwrap_or_default();
+    let perms = permissions(section.flags());
+    let bitness = options
+        .section_bitness
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|&(_, b)| b);
+    if output_mode > OutputMode::Normal {
+        let end = address + size;
+        print!(
+            "    {name} => 0x{address:x} - 0x{end:x}, offset 0x{offset:x}, {size} bytes, {perms}"
+        );
+        match bitness {
+            Some(bitness) => println!(", {bitness}-bit"),
+            _ => println!(),
+        }
+    }
+    if perms == "rwx" {
+        warnings::warn(
+            format!("section {name} is both writable and executable"),
+            true,
+        );
+    }
+
+    // Unknown permissions are given the benefit of the doubt.
+    if options.exec_only && (perms.contains('w') || perms.ends_with('-')) {
+        if output_mode > OutputMode::Normal {
+            println!("    Skipped {name}, writable or not executable");
+        }
+        return None;
+    }
+
+    Some(Segment {
+        offset,
+        size,
+        address,
+        bitness,
+    })
+}
+
+/// Maps an executable ELF program segment, used when there are no text sections.
+fn map_load_segment<'a>(
+    segment: &impl ObjectSegment<'a>,
+    exec_only: bool,
+    output_mode: OutputMode,
+) -> Option<Segment> {
+    let SegmentFlags::Elf { p_flags } = segment.flags() else {
+        return None;
+    };
+    if p_flags & elf::PF_X == 0 || (exec_only && p_flags & elf::PF_W != 0) {
+        return None;
+    }
+
+    let (offset, size) = segment.file_range();
+    if size == 0 {
+        return None;
+    }
+
+    if output_mode > OutputMode::Normal {
+        let address = segment.address();
+        let end = address + size;
+        println!("    PT_LOAD => 0x{address:x} - 0x{end:x}, offset 0x{offset:x}, {size} bytes");
+    }
+
+    Some(Segment {
+        offset,
+        size,
+        address: segment.address(),
+        bitness: None,
+    })
+}
+
+/// Coalesces adjacent and overlapping segments, so instructions crossing section boundaries
+/// are decoded correctly and overlapping bytes are not counted twice.
+pub fn merge_segments(mut segments: Vec<Segment>) -> Vec<Segment> {
+    segments.sort_by_key(|s| s.offset);
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        if let Some(last) = merged.last_mut() {
+            let end = last.offset + last.size;
+            // Only merge segments mapped contiguously in memory and decoded alike as well.
+            let contiguous =
+                segment.address.wrapping_sub(last.address) == segment.offset - last.offset;
+            if segment.offset <= end && contiguous && segment.bitness == last.bitness {
+                last.size = end.max(segment.offset + segment.size) - last.offset;
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+
+    merged
+}
+
+/// Collects function symbols located in text sections, sorted by address.
+fn read_symbols<'a>(file: &impl Object<'a>, relocatable: bool) -> Vec<Symbol> {
+    let mut symbols: Vec<_> = file
+        .symbols()
+        .chain(file.dynamic_symbols())
+        .filter(|s| s.kind() == SymbolKind::Text && s.size() > 0)
+        .filter_map(|s| {
+            let section = file.section_by_index(s.section_index()?).ok()?;
+            let (offset, size) = section.file_range()?;
+            let start = s.address().checked_sub(section.address())?;
+            let base = section_address(&section, relocatable)?;
+            (start < size).then(|| Symbol {
+                name: s.name().unwrap_or_default().to_string(),
+                offset: offset + start,
+                size: s.size().min(size - start),
+                address: base + start,
+            })
+        })
+        .collect();
+
+    // Exported functions are listed in both tables, aliases are kept to be found by name.
+    symbols.sort_by(|a, b| (a.address, a.size, &a.name).cmp(&(b.address, b.size, &b.name)));
+    symbols.dedup_by(|a, b| (a.address, a.size, &a.name) == (b.address, b.size, &b.name));
+    symbols
+}
+
+/// Type of `IMAGE_CHPE_RANGE_ENTRY` holding x86-64 code.
+const CHPE_RANGE_AMD64: u32 = 2;
+
+/// Finds x86-64 code ranges of a hybrid ARM64X PE binary in its CHPE metadata code map.
+fn hybrid_x64<'a>(
+    data: impl ReadRef<'a>,
+    image_base: u64,
+    output_mode: OutputMode,
+) -> Option<Vec<Segment>> {
+    let file = PeFile64::parse(data).ok()?;
+    let sections = file.section_table();
+    let directory = file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_LOAD_CONFIG)?;
+    let config = sections.pe_data_at(data, directory.virtual_address.get(LE))?;
+    let (config, _) = from_bytes::<pe::ImageLoadConfigDirectory64>(config).ok()?;
+    let metadata = config
+        .chpe_metadata_pointer
+        .get(LE)
+        .checked_sub(image_base)?;
+
+    // `IMAGE_ARM64EC_METADATA` starts with version, code map address and its size.
+    let metadata = sections.pe_data_at(data, metadata as u32)?;
+    let (header, _) = slice_from_bytes::<U32<LE>>(metadata, 3).ok()?;
+    let (code_map, count) = (header[1].get(LE), header[2].get(LE) as usize);
+    let code_map = sections.pe_data_at(data, code_map)?;
+    let (entries, _) = slice_from_bytes::<U32<LE>>(code_map, count * 2).ok()?;
+
+    if output_mode > OutputMode::Normal {
+        println!("Hybrid x86-64 code ranges: ");
+    }
 
-fn read_header<'a>(data: impl ReadRef<'a>, output_mode: OutputMode) -> R<Params> {
-    let file = object::File::parse(data)?;
+    // Range type is stored in the low bits of the start address.
+    let segments: Vec<_> = entries
+        .chunks_exact(2)
+        .map(|e| (e[0].get(LE), e[1].get(LE)))
+        .filter(|(start, _)| start & 3 == CHPE_RANGE_AMD64)
+        .filter_map(|(start, size)| {
+            let rva = start & !3;
+            let (offset, available) = sections.pe_file_range_at(rva)?;
+            let size = size.min(available) as u64;
+            let address = image_base + rva as u64;
+            if output_mode > OutputMode::Normal {
+                let end = address + size;
+                println!("    0x{address:x} - 0x{end:x}, offset 0x{offset:x}, {size} bytes");
+            }
+            Some(Segment {
+                offset: offset as u64,
+                size,
+                address,
+                bitness: None,
+            })
+        })
+        .collect();
+
+    (!segments.is_empty()).then_some(segments)
+}
+
+/// Whether the PE image can be loaded at a random address.
+fn pe_dynamic_base<'a>(data: impl ReadRef<'a>) -> Option<bool> {
+    let characteristics = match PeFile64::parse(data) {
+        Ok(file) => file.nt_headers().optional_header().dll_characteristics(),
+        _ => PeFile32::parse(data)
+            .ok()?
+            .nt_headers()
+            .optional_header()
+            .dll_characteristics(),
+    };
+    Some(characteristics & pe::IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE != 0)
+}
+
+/// Whether the binary is position-independent (PIE or shared library), unknown for objects.
+fn position_independent<'a>(
+    file: &impl Object<'a>,
+    format: BinaryFormat,
+    data: impl ReadRef<'a>,
+) -> Option<bool> {
+    match (format, file.kind()) {
+        (_, ObjectKind::Relocatable | ObjectKind::Unknown) => None,
+        (BinaryFormat::Pe, _) => pe_dynamic_base(data),
+        (_, ObjectKind::Dynamic) => Some(true),
+        (BinaryFormat::MachO, _) => match file.flags() {
+            FileFlags::MachO { flags } => Some(flags & macho::MH_PIE != 0),
+            _ => None,
+        },
+        (BinaryFormat::Elf, _) => Some(false),
+        _ => None,
+    }
+}
+
+fn read_object<'a>(
+    file: &impl Object<'a>,
+    format: BinaryFormat,
+    data: impl ReadRef<'a>,
+    options: Options,
+    output_mode: OutputMode,
+) -> R<Binary> {
+    let Options {
+        symbols,
+        arch,
+        exec_only,
+        ..
+    } = options;
     let architecture = file.architecture();
 
     if output_mode > OutputMode::Quiet {
-        println!("Format: {:?}", file.format());
+        println!("Format: {format:?}");
         println!("Architecture: {architecture:?}");
+        if let Some(pic) = position_independent(file, format, data) {
+            let pic = match pic {
+                true => "yes",
+                _ => "no",
+            };
+            println!("Position-independent: {pic}");
+        }
+    }
+
+    // ARM64X binaries may embed x86-64 code as well.
+    if architecture == Architecture::Aarch64
+        && format == BinaryFormat::Pe
+        && arch.is_none_or(|a| a == Architecture::X86_64)
+    {
+        if let Some(sections) = hybrid_x64(data, file.relative_address_base(), output_mode) {
+            return Ok(Binary {
+                sections: merge_segments(sections),
+                symbols: Vec::new(),
+                bitness: 64,
+            });
+        }
     }
 
     check!(
@@ -26,36 +396,202 @@ fn read_header<'a>(data: impl ReadRef<'a>, output_mode: OutputMode) -> R<Params>
         AppError::WrongArch,
     );
 
+    if let Some(arch) = arch {
+        check!(architecture == arch, AppError::ArchNotFound(arch));
+    }
+
+    let relocatable = file.kind() == ObjectKind::Relocatable;
+
     if output_mode > OutputMode::Normal {
+        if relocatable {
+            println!("Relocatable object, addresses are file offsets");
+        }
         println!("Text sections: ");
     }
 
-    let sections = file
+    let mut sections: Vec<_> = file
         .sections()
-        .filter_map(|s| match s.kind() {
-            SectionKind::Text => {
-                if output_mode > OutputMode::Normal {
-                    println!(
-                        "    {} => 0x{:x}, {} bytes",
-                        s.name().unwrap_or_default(),
-                        s.address(),
-                        s.size()
-                    );
-                }
-                s.file_range()
-            }
-            _ => None,
-        })
+        .filter_map(|s| map_segment(&s, relocatable, options, output_mode))
         .collect();
 
+    // Stripped ELF binaries may lack the section table, but program headers must survive.
+    if sections.is_empty() {
+        if output_mode > OutputMode::Normal {
+            println!("No text sections, using executable segments: ");
+        }
+        sections = file
+            .segments()
+            .filter_map(|s| map_load_segment(&s, exec_only, output_mode))
+            .collect();
+    }
+
+    let count = sections.len();
+    let sections = merge_segments(sections);
+    if output_mode > OutputMode::Normal && sections.len() < count {
+        println!("Merged into {} segments", sections.len());
+    }
+
+    let symbols = match symbols {
+        true => read_symbols(file, relocatable),
+        _ => Vec::new(),
+    };
+
+    if output_mode > OutputMode::Normal && !symbols.is_empty() {
+        println!("Function symbols: {}", symbols.len());
+    }
+
+    // x32 ABI uses 64-bit instruction encodings with 32-bit pointers.
     let bitness = match architecture {
-        Architecture::X86_64 => 64,
+        Architecture::X86_64 | Architecture::X86_64_X32 => 64,
         _ => 32,
     };
 
-    Ok((sections, bitness))
+    Ok(Binary {
+        sections,
+        symbols,
+        bitness,
+    })
+}
+
+/// Parses the object file, with the parser of `format` only if given instead of detecting it.
+fn read_header<'a>(data: impl ReadRef<'a>, options: Options, output_mode: OutputMode) -> R<Binary> {
+    let Some(format) = options.format else {
+        let file = object::File::parse(data)?;
+        return read_object(&file, file.format(), data, options, output_mode);
+    };
+
+    macro_rules! read {
+        ($($file: ty),+) => {{
+            $(if let Ok(file) = <$file>::parse(data) {
+                return read_object(&file, format, data, options, output_mode);
+            })+
+        }};
+    }
+
+    match format {
+        BinaryFormat::Elf => read!(ElfFile64<Endianness, _>, ElfFile32<Endianness, _>),
+        BinaryFormat::Pe => read!(PeFile64<_>, PeFile32<_>),
+        BinaryFormat::MachO => read!(MachOFile64<Endianness, _>, MachOFile32<Endianness, _>),
+        BinaryFormat::Coff => read!(CoffFile<_>, CoffBigFile<_>),
+        _ => {}
+    }
+
+    E!(AppError::WrongFormat(format))
 }
 
-pub fn parse(file: &fs::File, output_mode: OutputMode) -> R<Params> {
-    read_header(&ReadCache::new(file), output_mode)
+/// Treats the whole input as 64-bit code loaded at `base`.
+pub fn raw(file: &mut impl Source, base: u64) -> R<Binary> {
+    let size = file.seek(SeekFrom::End(0))?;
+    Ok(Binary {
+        sections: match size {
+            0 => Vec::new(),
+            _ => vec![Segment {
+                offset: 0,
+                size,
+                address: base,
+                bitness: None,
+            }],
+        },
+        symbols: Vec::new(),
+        bitness: 64,
+    })
+}
+
+/// Picks the slice of a fat binary matching `arch`, or the first x86 one.
+fn select_slice(arches: &[impl FatArch], arch: Option<Architecture>) -> R<(u64, u64)> {
+    let slice = arches.iter().find(|a| match arch {
+        Some(arch) => a.architecture() == arch,
+        _ => matches!(
+            a.architecture(),
+            Architecture::X86_64 | Architecture::X86_64_X32 | Architecture::I386
+        ),
+    });
+
+    match (slice, arch) {
+        (Some(slice), _) => Ok(slice.file_range()),
+        (_, Some(arch)) => E!(AppError::ArchNotFound(arch)),
+        _ => E!(AppError::WrongArch),
+    }
+}
+
+pub fn parse(file: &mut impl Source, options: Options, output_mode: OutputMode) -> R<Binary> {
+    let arch = options.arch;
+    let cache = ReadCache::new(file);
+
+    // A forced format may still be parsed when the magic bytes are not recognized.
+    let (offset, size) = match FileKind::parse(&cache) {
+        Ok(FileKind::MachOFat32) => select_slice(MachOFatFile32::parse(&cache)?.arches(), arch)?,
+        Ok(FileKind::MachOFat64) => select_slice(MachOFatFile64::parse(&cache)?.arches(), arch)?,
+        Err(e) if options.format.is_none() => Err(e)?,
+        _ => return read_header(&cache, options, output_mode),
+    };
+
+    if output_mode > OutputMode::Normal {
+        println!("Fat binary slice: 0x{offset:x}, {size} bytes");
+    }
+
+    // Offsets inside the slice are relative to its start.
+    let mut binary = read_header(cache.range(offset, size), options, output_mode)?;
+    for segment in &mut binary.sections {
+        segment.offset += offset;
+    }
+    for symbol in &mut binary.symbols {
+        symbol.offset += offset;
+    }
+    Ok(binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use iced_x86::Mnemonic;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::decoder::{self, Params};
+
+    fn segment(offset: u64, size: u64) -> Segment {
+        Segment {
+            offset,
+            size,
+            address: 0x1000 + offset,
+            bitness: None,
+        }
+    }
+
+    fn ranges(segments: &[Segment]) -> Vec<(u64, u64)> {
+        segments.iter().map(|s| (s.offset, s.size)).collect()
+    }
+
+    #[test]
+    fn merges_adjacent_and_overlapping_segments() {
+        let segments = vec![segment(8, 4), segment(0, 4), segment(4, 2), segment(5, 2)];
+        assert_eq!(ranges(&merge_segments(segments)), [(0, 7), (8, 4)]);
+    }
+
+    #[test]
+    fn keeps_segments_apart_in_memory() {
+        let mut moved = segment(4, 4);
+        moved.address += 0x100;
+        assert_eq!(
+            ranges(&merge_segments(vec![segment(0, 4), moved])),
+            [(0, 4), (4, 4)]
+        );
+    }
+
+    #[test]
+    fn decodes_instruction_across_sections_once() {
+        // `vpaddd ymm0, ymm0, ymm1` split between sections, then `ret`.
+        let code = [0xc5, 0xfd, 0xfe, 0xc1, 0xc3];
+        let sections = merge_segments(vec![segment(0, 2), segment(2, 3)]);
+        let mut mnemonics = Vec::new();
+        decoder::visit(
+            &mut Cursor::new(code),
+            &sections,
+            Params::default(),
+            OutputMode::Quiet,
+            |instruction, _| mnemonics.push(instruction.mnemonic()),
+        )
+        .unwrap();
+        assert_eq!(mnemonics, [Mnemonic::Vpaddd, Mnemonic::Ret]);
+    }
 }
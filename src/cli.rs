@@ -1,36 +1,601 @@
+use iced_x86::{CpuidFeature, Mnemonic};
+use object::{Architecture, BinaryFormat, SectionKind};
+use std::{env, fs, io, str::FromStr};
+
 use crate::{
-    error::{ArgError, R},
+    error::{AppError, ArgError, R},
+    profiles::{self, Profile},
     E,
 };
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OutputMode {
     Quiet,
+    /// Labels without notes and warnings.
+    Bare,
     Normal,
     Verbose,
 }
 
+/// Output modes in order of verbosity, indexed by numeric level.
+const OUTPUT_MODES: [OutputMode; 4] = [
+    OutputMode::Quiet,
+    OutputMode::Bare,
+    OutputMode::Normal,
+    OutputMode::Verbose,
+];
+
+impl OutputMode {
+    /// The mode `steps` levels more verbose than normal or this one, up to the most verbose.
+    fn louder(self, steps: usize) -> Self {
+        let level = self.max(OutputMode::Normal) as usize + steps;
+        OUTPUT_MODES[level.min(OUTPUT_MODES.len() - 1)]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sort {
+    /// Alphabetical.
+    Name,
+    /// Order of `CpuidFeature`, roughly by introduction.
+    Id,
+    /// Newest features first.
+    Era,
+}
+
+/// Features each instruction is counted toward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountBy {
+    /// Every feature of the instruction.
+    All,
+    /// The first one reported by the decoder.
+    Primary,
+    /// The one introduced most recently.
+    Newest,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    /// GitHub-flavored Markdown tables.
+    Markdown,
+    /// A JSON object per line.
+    Ndjson,
+    /// `id,count` rows keyed by `CpuidFeature` value, stable within an iced-x86 version.
+    IdCsv,
+}
+
+impl Format {
+    /// Whether the output is meant for other programs, not for reading.
+    pub fn is_machine(self) -> bool {
+        matches!(self, Format::Ndjson | Format::IdCsv)
+    }
+}
+
+/// Formatting of printed statistics.
+#[derive(Clone, Copy)]
+pub struct Style {
+    /// Fractional digits of ratios.
+    pub precision: usize,
+    /// Append proportional bars to feature counts.
+    pub bars: bool,
+    /// Group features by CPUID leaf.
+    pub by_leaf: bool,
+    /// Maximum number of mnemonics listed per feature.
+    pub top_mnemonics: Option<usize>,
+    pub format: Format,
+    pub sort: Sort,
+    /// Append iced-x86 enum ids to feature and mnemonic names.
+    pub with_ids: bool,
+    /// Indent JSON over multiple lines.
+    pub json_pretty: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Detect,
+    Details,
+    Top,
+    Level,
+    Legacy,
+    Privileged,
+    Cet,
+    Rng,
+    Tsx,
+    SseAvx,
+    Branches,
+    Stack,
+    Lengths,
+    Prefixes,
+    X64,
+    Mnemonics,
+    Categories,
+    Overlap,
+    Count,
+    Entropy,
+    Disasm,
+    InsnFeatures,
+}
+
 pub struct Config {
     pub file_path: Option<String>,
-    pub details: bool,
+    pub mode: Mode,
+    pub symbols: bool,
+    pub used_by: Option<CpuidFeature>,
+    pub symbol: Option<String>,
+    pub filter_feature: Option<CpuidFeature>,
+    pub samples: usize,
+    pub forbid: Vec<CpuidFeature>,
+    pub baseline: Option<Vec<CpuidFeature>>,
+    pub exit_code: bool,
+    pub check_invalid: bool,
+    pub amd: bool,
+    pub diff: Option<String>,
     pub output_mode: OutputMode,
+    pub bitness: Option<u32>,
+    /// Bitness of named sections.
+    pub section_bitness: Vec<(String, u32)>,
+    pub no_follow: bool,
+    pub gzip: bool,
+    pub count_by: CountBy,
+    pub style: Style,
+    pub profile: Option<&'static Profile>,
+    pub max_level: Option<&'static Profile>,
+    pub mnemonics: Vec<Mnemonic>,
+    pub excluded: Vec<Mnemonic>,
+    pub arch: Option<Architecture>,
+    pub format_hint: Option<BinaryFormat>,
+    pub raw: bool,
+    pub hex: Option<Vec<u8>>,
+    pub base: u64,
+    /// File offset to start decoding at.
+    pub decode_from: Option<u64>,
+    pub summary: bool,
+    pub trim_padding: bool,
+    pub ignore_padding: bool,
+    pub max_instructions: Option<u64>,
+    pub list_segments: bool,
+    pub exec_only: bool,
+    pub section_kinds: Vec<SectionKind>,
+    pub reachable_only: bool,
+    pub strict: bool,
+    pub jobs: usize,
+    pub progress: bool,
+    pub verify_parallel: bool,
+    pub pager: bool,
+    pub explain: Option<CpuidFeature>,
+    #[cfg(target_os = "linux")]
+    pub pid: Option<u32>,
+    pub analyze_self: bool,
+    /// Config file the default options were read from.
+    pub config_path: Option<String>,
 }
 
 impl Config {
     fn new() -> Self {
         Config {
             file_path: None,
-            details: false,
+            mode: Mode::Detect,
+            symbols: false,
+            used_by: None,
+            symbol: None,
+            filter_feature: None,
+            samples: 0,
+            forbid: Vec::new(),
+            baseline: None,
+            exit_code: false,
+            check_invalid: false,
+            amd: false,
+            diff: None,
             output_mode: OutputMode::Normal,
+            bitness: None,
+            section_bitness: Vec::new(),
+            no_follow: false,
+            gzip: false,
+            count_by: CountBy::All,
+            style: Style {
+                precision: 2,
+                bars: false,
+                by_leaf: false,
+                top_mnemonics: None,
+                format: Format::Text,
+                sort: Sort::Name,
+                with_ids: false,
+                json_pretty: false,
+            },
+            profile: None,
+            max_level: None,
+            mnemonics: Vec::new(),
+            excluded: Vec::new(),
+            arch: None,
+            format_hint: None,
+            raw: false,
+            hex: None,
+            base: 0,
+            decode_from: None,
+            summary: false,
+            trim_padding: false,
+            ignore_padding: false,
+            max_instructions: None,
+            list_segments: false,
+            exec_only: false,
+            section_kinds: vec![SectionKind::Text],
+            reachable_only: false,
+            strict: false,
+            jobs: 1,
+            progress: false,
+            verify_parallel: false,
+            pager: false,
+            explain: None,
+            #[cfg(target_os = "linux")]
+            pid: None,
+            analyze_self: false,
+            config_path: None,
+        }
+    }
+}
+
+fn parse_string(value: &str) -> Option<String> {
+    Some(value.to_string())
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+fn parse_mode(value: &str) -> Option<Mode> {
+    match value {
+        "detect" => Some(Mode::Detect),
+        "details" => Some(Mode::Details),
+        "top" => Some(Mode::Top),
+        "level" => Some(Mode::Level),
+        "legacy" => Some(Mode::Legacy),
+        "privileged" => Some(Mode::Privileged),
+        "cet" => Some(Mode::Cet),
+        "rng" => Some(Mode::Rng),
+        "tsx" => Some(Mode::Tsx),
+        "sse-avx" => Some(Mode::SseAvx),
+        "branches" => Some(Mode::Branches),
+        "stack" => Some(Mode::Stack),
+        "lengths" => Some(Mode::Lengths),
+        "prefixes" => Some(Mode::Prefixes),
+        "x64" => Some(Mode::X64),
+        "mnemonics" => Some(Mode::Mnemonics),
+        "categories" => Some(Mode::Categories),
+        "overlap" => Some(Mode::Overlap),
+        "count" => Some(Mode::Count),
+        "entropy" => Some(Mode::Entropy),
+        "disasm" => Some(Mode::Disasm),
+        "insn-features" => Some(Mode::InsnFeatures),
+        _ => None,
+    }
+}
+
+fn parse_format(value: &str) -> Option<Format> {
+    match value {
+        "text" => Some(Format::Text),
+        "markdown" => Some(Format::Markdown),
+        "ndjson" => Some(Format::Ndjson),
+        "id-csv" => Some(Format::IdCsv),
+        _ => None,
+    }
+}
+
+fn parse_output_mode(value: &str) -> Option<OutputMode> {
+    match value {
+        "quiet" => Some(OutputMode::Quiet),
+        "bare" => Some(OutputMode::Bare),
+        "normal" => Some(OutputMode::Normal),
+        "verbose" => Some(OutputMode::Verbose),
+        _ => OUTPUT_MODES.get(parse_number::<usize>(value)?).copied(),
+    }
+}
+
+fn parse_count_by(value: &str) -> Option<CountBy> {
+    match value {
+        "all" => Some(CountBy::All),
+        "primary" => Some(CountBy::Primary),
+        "newest" => Some(CountBy::Newest),
+        _ => None,
+    }
+}
+
+fn parse_sort(value: &str) -> Option<Sort> {
+    match value {
+        "name" => Some(Sort::Name),
+        "id" => Some(Sort::Id),
+        "era" => Some(Sort::Era),
+        _ => None,
+    }
+}
+
+/// Whether the vendor is AMD.
+fn parse_vendor(value: &str) -> Option<bool> {
+    match value {
+        "intel" => Some(false),
+        "amd" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_arch(value: &str) -> Option<Architecture> {
+    match value {
+        "x86_64" => Some(Architecture::X86_64),
+        "x32" => Some(Architecture::X86_64_X32),
+        "i386" => Some(Architecture::I386),
+        _ => None,
+    }
+}
+
+fn parse_binary_format(value: &str) -> Option<BinaryFormat> {
+    match value {
+        "elf" => Some(BinaryFormat::Elf),
+        "pe" => Some(BinaryFormat::Pe),
+        "macho" => Some(BinaryFormat::MachO),
+        "coff" => Some(BinaryFormat::Coff),
+        _ => None,
+    }
+}
+
+fn parse_kind(value: &str) -> Option<SectionKind> {
+    match value {
+        "text" => Some(SectionKind::Text),
+        "data" => Some(SectionKind::Data),
+        "read-only-data" => Some(SectionKind::ReadOnlyData),
+        "read-only-data-with-rel" => Some(SectionKind::ReadOnlyDataWithRel),
+        "read-only-string" => Some(SectionKind::ReadOnlyString),
+        "tls" => Some(SectionKind::Tls),
+        "other" => Some(SectionKind::Other),
+        "unknown" => Some(SectionKind::Unknown),
+        _ => None,
+    }
+}
+
+fn parse_kinds(value: &str) -> Option<Vec<SectionKind>> {
+    value.split(',').map(parse_kind).collect()
+}
+
+fn parse_address(value: &str) -> Option<u64> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    u64::from_str_radix(value, 16).ok()
+}
+
+/// Hex string of code bytes, whitespace between them is allowed.
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let digits: Vec<_> = value.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn parse_bitness(value: &str) -> Option<u32> {
+    match value {
+        "16" => Some(16),
+        "32" => Some(32),
+        "64" => Some(64),
+        _ => None,
+    }
+}
+
+fn parse_feature(value: &str) -> Option<CpuidFeature> {
+    CpuidFeature::values().find(|f| format!("{f:?}").eq_ignore_ascii_case(value))
+}
+
+/// Parses `NAME=BITS` section bitness.
+fn parse_section_bitness(value: &str) -> Option<(String, u32)> {
+    let (name, bitness) = value.split_once('=')?;
+    Some((name.to_string(), parse_bitness(bitness)?))
+}
+
+fn parse_features(value: &str) -> Option<Vec<CpuidFeature>> {
+    value.split(',').map(parse_feature).collect()
+}
+
+/// Case-insensitive match supporting `*` and `?` wildcards.
+/// On a mismatch, only the last `*` is retried one byte further, which keeps it linear.
+pub fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Positions after the last `*` and of the text it was retried at.
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                star = Some((p, t));
+            }
+            Some(&a) if a == b'?' || a.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((after, retry)) => {
+                    p = after;
+                    t = retry + 1;
+                    star = Some((after, t));
+                }
+                _ => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+fn parse_mnemonics(value: &str) -> Option<Vec<Mnemonic>> {
+    let mnemonics: Vec<_> = Mnemonic::values()
+        .filter(|m| glob(value.as_bytes(), format!("{m:?}").as_bytes()))
+        .collect();
+    (!mnemonics.is_empty()).then_some(mnemonics)
+}
+
+/// Default instructions limit of `insn-features` mode.
+const INSN_FEATURES_LIMIT: u64 = 1000;
+
+/// Default options file, looked up in the working directory.
+const CONFIG_FILE: &str = ".bincpuflags.toml";
+
+/// Reads a string, number or boolean at the start of `text`, returning the rest.
+/// Numbers and booleans are passed on as written.
+fn toml_scalar(text: &str) -> Result<(String, &str), &'static str> {
+    let text = text.trim_start();
+    match text.chars().next() {
+        Some('"') => {
+            let mut value = String::new();
+            let mut chars = text.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Ok((value, &text[i + 1..])),
+                    '\\' => value.push(match chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'n')) => '\n',
+                        _ => return Err("unsupported escape sequence"),
+                    }),
+                    c => value.push(c),
+                }
+            }
+            Err("unterminated string")
+        }
+        Some('\'') => match text[1..].split_once('\'') {
+            Some((value, rest)) => Ok((value.into(), rest)),
+            _ => Err("unterminated string"),
+        },
+        _ => {
+            let end = text
+                .find(|c: char| c.is_whitespace() || matches!(c, ',' | ']' | '#'))
+                .unwrap_or(text.len());
+            let (value, rest) = text.split_at(end);
+            match value {
+                "" => Err("missing value"),
+                "true" | "false" => Ok((value.into(), rest)),
+                _ if value.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '+' | '-')) => {
+                    Ok((value.into(), rest))
+                }
+                _ => Err("strings must be quoted"),
+            }
+        }
+    }
+}
+
+/// Reads a value at the start of `text`, returning the rest.
+/// Items of an array, which must fit on one line, are joined with commas.
+fn toml_value(text: &str) -> Result<(String, &str), &'static str> {
+    let Some(mut rest) = text.trim_start().strip_prefix('[') else {
+        return toml_scalar(text);
+    };
+    let mut items = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(rest) = rest.strip_prefix(']') {
+            return Ok((items.join(","), rest));
+        }
+        if rest.is_empty() || rest.starts_with('#') {
+            return Err("unterminated array");
+        }
+        let (item, after) = toml_scalar(rest)?;
+        items.push(item);
+        rest = after.trim_start();
+        match rest.strip_prefix(',') {
+            Some(after) => rest = after,
+            _ if rest.starts_with(']') => {}
+            _ if rest.is_empty() || rest.starts_with('#') => return Err("unterminated array"),
+            _ => return Err("expected ',' or ']' in array"),
+        }
+    }
+}
+
+/// Reads the config file as long options preceding the command line ones, so the command line
+/// overrides them. The file is a flat subset of TOML: `key = value` lines with `#` comments,
+/// values being strings, numbers, booleans or arrays. `true` values stand for flags, `false`
+/// ones are skipped. Returns the path of the file read, a missing default file is not an error.
+fn read_config(args: &[String]) -> R<(Option<String>, Vec<String>)> {
+    let options: Vec<_> = args.iter().take_while(|a| *a != "--").collect();
+    let path = options
+        .iter()
+        .position(|a| *a == "--config")
+        .and_then(|i| options.get(i + 1));
+
+    if options.iter().any(|a| *a == "--no-config") {
+        if path.is_some() {
+            E!(ArgError::Conflict("--no-config".into(), "--config".into()))
+        }
+        return Ok((None, Vec::new()));
+    }
+
+    let path = path.map_or(CONFIG_FILE, |p| p.as_str());
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if path == CONFIG_FILE && e.kind() == io::ErrorKind::NotFound => {
+            return Ok((None, Vec::new()))
+        }
+        Err(e) => E!(AppError::Io(path.into(), e)),
+    };
+
+    let mut options = Vec::new();
+    let mut keys = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let error = |reason| ArgError::Config(path.into(), number + 1, reason);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            E!(error("tables are not supported"))
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            E!(error("expected 'key = value'"))
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+            E!(error("invalid key"))
+        }
+        if keys.contains(&key) {
+            E!(error("duplicate key"))
+        }
+        keys.push(key);
+        let (value, rest) = toml_value(value).map_err(error)?;
+        let rest = rest.trim_start();
+        if !(rest.is_empty() || rest.starts_with('#')) {
+            E!(error("unexpected text after value"))
+        }
+        let key = format!("--{key}");
+        match value.as_str() {
+            "true" => options.push(key),
+            "false" => {}
+            _ => options.extend([key, value]),
         }
     }
+    Ok((Some(path.into()), options))
 }
 
 pub fn read_args(args: impl Iterator<Item = String>) -> R<Option<Config>> {
+    let args: Vec<_> = args.collect();
+    let (config_path, options) = read_config(&args)?;
+    let mut args = options.into_iter().chain(args);
     let mut config = Config::new();
+    config.config_path = config_path;
     let mut read_options = true;
 
-    for arg in args {
+    macro_rules! value {
+        ($arg: expr, $parse: expr) => {{
+            let value = match args.next() {
+                Some(value) => value,
+                _ => E!(ArgError::NoValue($arg.clone())),
+            };
+            match $parse(value.as_str()) {
+                Some(v) => v,
+                _ => E!(ArgError::InvalidValue($arg.clone(), value)),
+            }
+        }};
+    }
+
+    while let Some(arg) = args.next() {
         if arg.is_empty() {
             continue;
         }
@@ -39,14 +604,172 @@ pub fn read_args(args: impl Iterator<Item = String>) -> R<Option<Config>> {
             continue;
         }
         match arg.as_str().trim() {
-            "-d" | "--details" => config.details = true,
-            "-v" | "--verbose" => config.output_mode = OutputMode::Verbose,
+            "-m" | "--mode" => config.mode = value!(arg, parse_mode),
+            "-d" | "--details" => config.mode = Mode::Details,
+            "-s" | "--symbols" => config.symbols = true,
+            "-u" | "--used-by" => config.used_by = Some(value!(arg, parse_feature)),
+            "-S" | "--symbol" => config.symbol = Some(value!(arg, parse_string)),
+            "--filter-feature" => config.filter_feature = Some(value!(arg, parse_feature)),
+            "--samples" => config.samples = value!(arg, parse_number),
+            "-f" | "--forbid" => config.forbid = value!(arg, parse_features),
+            "--baseline-level" => {
+                config.baseline = Some(value!(arg, profiles::find_level).supported())
+            }
+            "--baseline-features" => config.baseline = Some(value!(arg, parse_features)),
+            "-x" | "--exit-code" => config.exit_code = true,
+            "--explain" => config.explain = Some(value!(arg, parse_feature)),
+            "-D" | "--diff" => config.diff = Some(value!(arg, parse_string)),
+            "-a" | "--arch" => config.arch = Some(value!(arg, parse_arch)),
+            "--format-hint" => config.format_hint = Some(value!(arg, parse_binary_format)),
+            "-r" | "--raw" => config.raw = true,
+            "--hex" => config.hex = Some(value!(arg, parse_hex)),
+            #[cfg(target_os = "linux")]
+            "--pid" => config.pid = Some(value!(arg, parse_number)),
+            #[cfg(not(target_os = "linux"))]
+            "--pid" => E!(ArgError::Unsupported(arg)),
+            "--analyze-self" => config.analyze_self = true,
+            "--base" => config.base = value!(arg, parse_address),
+            "--decode-from" => config.decode_from = Some(value!(arg, parse_address)),
+            "-b" | "--bitness" => config.bitness = Some(value!(arg, parse_bitness)),
+            "--section-bitness" => config
+                .section_bitness
+                .push(value!(arg, parse_section_bitness)),
+            "--strict" => config.strict = true,
+            "--check-invalid" => config.check_invalid = true,
+            // `--amd` is short for `--vendor amd`.
+            "--vendor" | "--amd" => config.amd = arg == "--amd" || value!(arg, parse_vendor),
+            "--count-only-reachable" => config.reachable_only = true,
+            "--exec-only" => config.exec_only = true,
+            "--sections-kind" => config.section_kinds = value!(arg, parse_kinds),
+            "--trim-padding" => config.trim_padding = true,
+            "--ignore-padding-insns" => config.ignore_padding = true,
+            "--list-segments" => config.list_segments = true,
+            "--max-instructions" => config.max_instructions = Some(value!(arg, parse_number)),
+            "-j" | "--jobs" => config.jobs = value!(arg, parse_number),
+            "--progress" => config.progress = true,
+            "--verify-parallel" => config.verify_parallel = true,
+            "--pager" => config.pager = true,
+            "--no-follow" => config.no_follow = true,
+            "-z" | "--gzip" => config.gzip = true,
+            "--no-overlap" => config.count_by = CountBy::Primary,
+            "--count-by" => config.count_by = value!(arg, parse_count_by),
+            "--precision" => config.style.precision = value!(arg, parse_number),
+            "--bars" => config.style.bars = true,
+            "--by-leaf" => config.style.by_leaf = true,
+            "--mnemonic" => config.mnemonics = value!(arg, parse_mnemonics),
+            "--exclude-mnemonic" => config.excluded.extend(value!(arg, parse_mnemonics)),
+            "--top-mnemonics" => config.style.top_mnemonics = Some(value!(arg, parse_number)),
+            "--format" => config.style.format = value!(arg, parse_format),
+            "--with-ids" => config.style.with_ids = true,
+            "--json-pretty" => {
+                config.style.format = Format::Ndjson;
+                config.style.json_pretty = true;
+            }
+            "--sort" => config.style.sort = value!(arg, parse_sort),
+            "--summary" => config.summary = true,
+            "-p" | "--profile" => config.profile = Some(value!(arg, profiles::find)),
+            "--max-level" => config.max_level = Some(value!(arg, profiles::find_level)),
+            "--verbose" => config.output_mode = config.output_mode.louder(1),
+            // Stacked `-v`, `-vv`, etc., each one level more.
+            a if a.len() > 1 && a[1..].bytes().all(|b| b == b'v') => {
+                config.output_mode = config.output_mode.louder(a.len() - 1)
+            }
             "-q" | "--quiet" => config.output_mode = OutputMode::Quiet,
+            "--bare" => config.output_mode = OutputMode::Bare,
+            "--output" => config.output_mode = value!(arg, parse_output_mode),
+            // Already read before other options.
+            "--config" => _ = value!(arg, parse_string),
+            "--no-config" => {}
             "-h" | "--help" => return Ok(None),
             "--" => read_options = false,
             _ => E!(ArgError::Unknown(arg)),
         }
     }
 
+    // Anything besides JSON lines or CSV rows would break the stream.
+    if config.style.format.is_machine() {
+        config.output_mode = OutputMode::Quiet;
+    }
+
+    // Rows always carry counts.
+    if config.style.format == Format::IdCsv && config.mode == Mode::Detect {
+        config.mode = Mode::Details;
+    }
+
+    // Only features of the whole file are printed in other formats.
+    if config.style.format != Format::Text {
+        let features = matches!(config.mode, Mode::Detect | Mode::Details);
+        let text_only = [
+            (!features, "--mode"),
+            (config.diff.is_some(), "--diff"),
+            (config.used_by.is_some(), "--used-by"),
+            (config.symbols, "--symbols"),
+            (config.list_segments, "--list-segments"),
+        ];
+        if let Some(&(_, option)) = text_only.iter().find(|(used, _)| *used) {
+            E!(ArgError::Conflict("--format".into(), option.into()))
+        }
+    }
+
+    // Memory file of the process stands in for the target file.
+    #[cfg(target_os = "linux")]
+    if let Some(pid) = config.pid {
+        let sources = [
+            (config.hex.is_some(), "--hex"),
+            (config.analyze_self, "--analyze-self"),
+        ];
+        let other = match sources.iter().find(|(used, _)| *used) {
+            Some(&(_, option)) => Some(option.into()),
+            _ => config.file_path,
+        };
+        if let Some(other) = other {
+            E!(ArgError::Conflict("--pid".into(), other))
+        }
+        config.file_path = Some(format!("/proc/{pid}/mem"));
+    }
+
+    // Code bytes from the command line stand in for the target file.
+    if config.hex.is_some() {
+        if let Some(path) = config.file_path {
+            E!(ArgError::Conflict("--hex".into(), path))
+        }
+        config.raw = true;
+        config.file_path = Some("<hex>".into());
+    }
+
+    // Own executable stands in for the target file, a check that the tool works here.
+    if config.analyze_self {
+        if let Some(path) = config.file_path {
+            E!(ArgError::Conflict("--analyze-self".into(), path))
+        }
+        config.file_path = Some(env::current_exe()?.to_string_lossy().into());
+    }
+
+    // Per-instruction output is meant for small inputs.
+    if config.mode == Mode::InsnFeatures {
+        config.max_instructions.get_or_insert(INSN_FEATURES_LIMIT);
+    }
+
     Ok(Some(config))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(glob(b"*6crypto*", b"_ZN6crypto4hash17h0123E"));
+        assert!(glob(b"vp?dd*", b"VPADDD"));
+        assert!(glob(b"a*b*", b"aXbYb"));
+        assert!(glob(b"**", b""));
+        assert!(!glob(b"a*b", b"aXbY"));
+        assert!(!glob(b"?", b""));
+    }
+
+    #[test]
+    fn glob_stars_linear() {
+        let name = [b'a'; 60];
+        assert!(!glob(b"*a*a*a*a*a*a*a*a*a*a*a*b", &name));
+    }
+}
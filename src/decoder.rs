@@ -1,114 +1,1938 @@
-use iced_x86::{CpuidFeature, Decoder, DecoderOptions, Mnemonic};
+use iced_x86::{
+    CpuidFeature, Decoder, DecoderError, Formatter, Instruction, IntelFormatter, Mnemonic, Register,
+};
 use std::{
-    collections::HashSet,
-    fs::File,
-    io::{Read, Seek, SeekFrom},
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    io::{self, SeekFrom},
+    ops::Range,
+    panic, slice, thread,
 };
 
-use crate::{cli::OutputMode, error::R};
+use crate::{
+    binary::{Segment, Source, Symbol},
+    cli::{CountBy, Format, OutputMode, Sort, Style},
+    error::{AppError, R},
+    leaves, profiles,
+    progress::Progress,
+    tasks::{ratio, Task},
+    warnings, E,
+};
 
-/// Should be bigger or equal to `IcedConstants::CPUID_FEATURE_ENUM_COUNT`.
-/// The crate does not export it unfortunatelty.
-const CF_COUNT: usize = 256;
+/// Size of per-feature tables, `IcedConstants::CPUID_FEATURE_ENUM_COUNT` not being exported.
+fn cf_count() -> usize {
+    CpuidFeature::values().len()
+}
 
-type Detail = HashSet<Mnemonic>;
+/// Features available on any x86-64 CPU, not worth mentioning per function.
+pub const BASELINE: &[CpuidFeature] = {
+    use CpuidFeature::*;
+    &[
+        INTEL8086,
+        INTEL186,
+        INTEL286,
+        INTEL386,
+        INTEL486,
+        X64,
+        CMOV,
+        CX8,
+        FPU,
+        FPU287,
+        FPU387,
+        FXSR,
+        MMX,
+        MULTIBYTENOP,
+        PAUSE,
+        SSE,
+        SSE2,
+        SYSCALL,
+        TSC,
+    ]
+};
 
-fn decode(data: &[u8], bitness: u32, found: &mut [bool], details: Option<&mut [Detail]>) {
-    let decoder = Decoder::new(bitness, data, DecoderOptions::NO_INVALID_CHECK);
+/// Vector register classes, indexed by width rank.
+const VECTORS: &[&str] = &["None", "XMM (SSE)", "YMM (AVX)", "ZMM (AVX-512)"];
 
-    macro_rules! body {
-        ($($d: expr)?) => {
-            for instruction in decoder {
-                for &feature in instruction.cpuid_features() {
-                    let index = feature as usize;
-                    if let Some(flag) = found.get_mut(index) {
-                        *flag = true;
-                        $(if let Some(d) = $d.get_mut(index) {
-                            d.insert(instruction.mnemonic());
-                        })?
-                    }
-                }
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Detail {
+    count: u64,
+    mnemonics: HashMap<Mnemonic, u64>,
+    samples: Vec<u64>,
+    /// Number of segments with instructions of the feature.
+    segments: usize,
+    /// Address and count of the segment with the most instructions of the feature.
+    dominant: (u64, u64),
+}
+
+/// Updates dominant segments of features with their counts in the segment.
+fn attribute(details: &mut [Detail], counts: &[u64], segment: &Segment) {
+    for (d, &count) in details.iter_mut().zip(counts) {
+        if count == 0 {
+            continue;
+        }
+        d.segments += 1;
+        if count > d.dominant.1 {
+            d.dominant = (segment.address, count);
+        }
+    }
+}
+
+/// Options of details mode.
+#[derive(Debug, Default, PartialEq)]
+pub struct DetailOptions {
+    /// Number of sample addresses collected per feature.
+    pub samples: usize,
+    /// Only count these mnemonics, all if empty.
+    pub mnemonics: Vec<Mnemonic>,
+}
+
+/// Features found in the file, with optional per-feature details.
+struct Features {
+    found: Vec<bool>,
+    details: Option<Vec<Detail>>,
+    /// Total number of decoded instructions.
+    total: u64,
+    invalid: u64,
+    /// Rank of the widest vector register used, see `VECTORS`.
+    vector: usize,
+    /// Instructions masked by each AVX-512 opmask register, `K0` meaning no mask.
+    opmasks: [u64; 8],
+    /// Number of `cpuid` instructions.
+    cpuid: u64,
+    /// Number of segments with decoded instructions.
+    segments: usize,
+    /// Address and number of features of the segment using the most distinct features.
+    diverse: (u64, usize),
+    decoded: Decoded,
+}
+
+impl Features {
+    /// Adds results of decoding the segment.
+    fn add(&mut self, part: Part<Tally>, segment: &Segment) {
+        if part.total > 0 {
+            self.segments += 1;
+            let count = part.found.iter().filter(|&&f| f).count();
+            if count > self.diverse.1 {
+                self.diverse = (segment.address, count);
             }
+        }
+        for (flag, found) in self.found.iter_mut().zip(part.found) {
+            *flag |= found;
+        }
+        self.total += part.total;
+        self.invalid += part.invalid;
+
+        let tally = part.task;
+        self.vector = self.vector.max(tally.vector);
+        for (count, other) in self.opmasks.iter_mut().zip(tally.opmasks) {
+            *count += other;
+        }
+        self.cpuid += tally.cpuid;
+        if let (Some(details), Some(other)) = (&mut self.details, tally.details) {
+            let counts: Vec<_> = other.iter().map(|d| d.count).collect();
+            merge_details(details, other, tally.options.samples);
+            attribute(details, &counts, segment);
+        }
+    }
+}
+
+/// Totals of a run, for the summary line.
+pub struct Summary {
+    pub found: Vec<CpuidFeature>,
+    pub total: u64,
+    pub invalid: u64,
+    /// Instructions per feature in details mode.
+    pub counts: Option<Vec<u64>>,
+    /// Padding instructions skipped with `Params::ignore_padding`.
+    pub padding: u64,
+    /// Whether decoding stopped at `Params::max_instructions`.
+    pub sampled: bool,
+}
+
+/// Instructions decoded by a run, analyzed or skipped.
+#[derive(Clone, Copy, Default)]
+pub struct Decoded {
+    /// Instructions passed on for analysis.
+    pub instructions: u64,
+    /// Padding instructions skipped with `Params::ignore_padding`.
+    pub padding: u64,
+    /// Whether decoding stopped at `Params::max_instructions`.
+    pub sampled: bool,
+}
+
+impl Decoded {
+    fn add(&mut self, other: Decoded) {
+        self.instructions += other.instructions;
+        self.padding += other.padding;
+    }
+}
+
+/// Decoding parameters shared by all modes.
+#[derive(Clone, Copy)]
+pub struct Params<'a> {
+    pub bitness: u32,
+    /// `iced_x86::DecoderOptions` flags.
+    pub options: u32,
+    /// Features each instruction is counted toward.
+    pub count_by: CountBy,
+    /// Skip trailing padding of segments.
+    pub trim_padding: bool,
+    /// Threads to decode large segments with.
+    pub jobs: usize,
+    /// Skip alignment padding instructions.
+    pub ignore_padding: bool,
+    /// Stop decoding after this many instructions.
+    pub max_instructions: Option<u64>,
+    /// Mnemonics left out of all counts.
+    pub excluded: &'a [Mnemonic],
+    /// Features not worth reporting as extensions of the target,
+    /// `BASELINE` unless set with `--baseline-level` or `--baseline-features`.
+    pub baseline: &'a [CpuidFeature],
+    /// Receives decoding progress.
+    pub progress: Option<&'a dyn Progress>,
+    /// Check parallel decoding against sequential decoding.
+    pub verify_parallel: bool,
+}
+
+/// Sequential 64-bit decoding counting everything.
+#[cfg(test)]
+impl Default for Params<'_> {
+    fn default() -> Self {
+        Params {
+            bitness: 64,
+            options: iced_x86::DecoderOptions::NO_INVALID_CHECK,
+            count_by: CountBy::All,
+            trim_padding: false,
+            jobs: 1,
+            ignore_padding: false,
+            max_instructions: None,
+            excluded: &[],
+            baseline: BASELINE,
+            progress: None,
+            verify_parallel: false,
+        }
+    }
+}
+
+/// Whether the instruction is likely alignment padding between functions.
+fn is_padding(instruction: &Instruction, params: Params) -> bool {
+    params.ignore_padding && matches!(instruction.mnemonic(), Mnemonic::Int3 | Mnemonic::Nop)
+}
+
+/// Features the instruction is counted toward.
+/// The first one reported by the decoder is the primary one, the newest one is the most
+/// restrictive requirement (the primary one on ties).
+fn counted_features(instruction: &Instruction, count_by: CountBy) -> &'static [CpuidFeature] {
+    let features = instruction.cpuid_features();
+    match count_by {
+        CountBy::All => features,
+        CountBy::Primary => &features[..features.len().min(1)],
+        CountBy::Newest => features
+            .iter()
+            .rev()
+            .max_by_key(|&&f| profiles::era(f))
+            .map_or(&[], slice::from_ref),
+    }
+}
+
+/// Rank of the widest vector register among operands, including VSIB index.
+fn vector_width(instruction: &Instruction) -> usize {
+    (0..instruction.op_count())
+        .map(|i| instruction.op_register(i))
+        .chain([instruction.memory_index()])
+        .map(|r| match r {
+            _ if r.is_zmm() => 3,
+            _ if r.is_ymm() => 2,
+            _ if r.is_xmm() => 1,
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Number of the opmask register masking the instruction, 0 if unmasked.
+fn opmask(instruction: &Instruction) -> usize {
+    match instruction.op_mask() {
+        Register::None => 0,
+        r => r as usize - Register::K0 as usize,
+    }
+}
+
+/// Opmask registers used for masking with their counts, genuine masked AVX-512 code
+/// as opposed to plain 512-bit moves.
+fn print_opmasks(features: &Features) {
+    if features.opmasks[1..].iter().all(|&c| c == 0) {
+        return;
+    }
+    print!("Opmask registers:");
+    for (i, count) in features.opmasks.iter().enumerate().skip(1) {
+        if *count > 0 {
+            print!(" K{i} ({count})");
+        }
+    }
+    println!();
+}
+
+/// Strips a trailing run of padding bytes, if long enough to surely not be code.
+fn trim_padding<'a>(data: &'a [u8], segment: &Segment, output_mode: OutputMode) -> &'a [u8] {
+    const PADDING: &[u8] = &[0x00, 0xcc, 0x90];
+    const MIN_RUN: usize = 16;
+
+    let Some(last) = data.last().filter(|b| PADDING.contains(b)) else {
+        return data;
+    };
+    let run = data.iter().rev().take_while(|b| *b == last).count();
+    if run < MIN_RUN {
+        return data;
+    }
+
+    let end = data.len() - run;
+    if output_mode > OutputMode::Normal {
+        println!(
+            "Note: trimmed {run} bytes of 0x{last:02x} padding at 0x{:x}",
+            segment.address + end as u64
+        );
+    }
+    &data[..end]
+}
+
+/// Run of identical instructions, long ones suggest data decoded as code.
+#[derive(Default)]
+struct Repeats<'a> {
+    address: u64,
+    bytes: &'a [u8],
+    count: u64,
+}
+
+impl<'a> Repeats<'a> {
+    /// Shortest run worth reporting, well above usual alignment padding.
+    const MIN_COUNT: u64 = 64;
+
+    fn add(&mut self, address: u64, bytes: &'a [u8]) {
+        if bytes == self.bytes {
+            self.count += 1;
+            return;
+        }
+        self.report();
+        *self = Repeats {
+            address,
+            bytes,
+            count: 1,
         };
     }
 
-    match details {
-        Some(d) => body!(d),
-        _ => body!(),
+    fn report(&self) {
+        if self.count < Self::MIN_COUNT {
+            return;
+        }
+        let end = self.address + self.count * self.bytes.len() as u64;
+        print!(
+            "Note: {} repeated instructions at 0x{:x} - 0x{end:x}, possibly data: ",
+            self.count, self.address
+        );
+        for b in self.bytes {
+            print!("{b:02x}");
+        }
+        println!();
+    }
+}
+
+fn cut_off_warning(ip: u64, segment: &Segment) {
+    warnings::warn(
+        format!(
+            "instruction at 0x{ip:x} cut off by the end of segment at 0x{:x}",
+            segment.address,
+        ),
+        true,
+    );
+}
+
+/// Instructions decoded between progress bar updates.
+const PROGRESS_STEP: u64 = 1 << 16;
+
+/// Decodes the segment with instruction pointer set to its virtual address.
+/// An instruction cut off by the end of the segment is skipped and reported in verbose mode,
+/// along with the code density and runs of repeated instructions.
+fn decode_segment(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    output_mode: OutputMode,
+    mut f: impl FnMut(&Instruction),
+) -> Decoded {
+    let data = match params.trim_padding {
+        true => trim_padding(data, segment, output_mode),
+        _ => data,
+    };
+    let bitness = segment.bitness.unwrap_or(params.bitness);
+    let mut decoder = Decoder::with_ip(bitness, data, segment.address, params.options);
+    let mut instruction = Instruction::default();
+    let mut count = 0;
+    let mut padding = 0;
+    // Trimmed padding and a cut off instruction are left out.
+    let mut decoded = 0;
+    let mut repeats = Repeats::default();
+    let verbose = output_mode > OutputMode::Normal;
+    let limit = params.max_instructions.unwrap_or(u64::MAX);
+
+    while decoder.can_decode() && count < limit {
+        decoder.decode_out(&mut instruction);
+
+        if decoder.last_error() == DecoderError::NoMoreBytes {
+            cut_off_warning(instruction.ip(), segment);
+            break;
+        }
+        decoded += instruction.len();
+        if is_padding(&instruction, params) {
+            padding += 1;
+            continue;
+        }
+        if params.excluded.contains(&instruction.mnemonic()) {
+            continue;
+        }
+
+        count += 1;
+        if let Some(progress) = params.progress.filter(|_| count % PROGRESS_STEP == 0) {
+            progress.update(decoder.position() as u64);
+        }
+        if verbose {
+            let start = (instruction.ip() - segment.address) as usize;
+            repeats.add(instruction.ip(), &data[start..start + instruction.len()]);
+        }
+        f(&instruction);
+    }
+
+    repeats.report();
+
+    // Density of a sampled segment is meaningless.
+    if verbose && !data.is_empty() && count < limit {
+        println!(
+            "Segment 0x{:x}: {count} instructions, {:.2} per byte, decoded {decoded} of {} bytes",
+            segment.address,
+            count as f64 / data.len() as f64,
+            segment.size
+        );
+    }
+
+    Decoded {
+        instructions: count,
+        padding,
+        sampled: false,
+    }
+}
+
+/// Attributes instructions to the function symbols containing them.
+fn decode_symbols(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    symbols: &[Symbol],
+    output_mode: OutputMode,
+    mut f: impl FnMut(usize, &Instruction),
+) -> Decoded {
+    let mut index = symbols.partition_point(|s| s.end() <= segment.address);
+
+    decode_segment(data, segment, params, output_mode, |instruction| {
+        let ip = instruction.ip();
+        while symbols.get(index).is_some_and(|s| s.end() <= ip) {
+            index += 1;
+        }
+        if symbols.get(index).is_some_and(|s| s.address <= ip) {
+            f(index, instruction);
+        }
+    })
+}
+
+/// Reads segments one by one, until the instructions limit is reached.
+/// `f` gets the instructions limit left for the segment in its parameters.
+fn read_sections(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    mut f: impl FnMut(&[u8], &Segment, Params) -> Decoded,
+) -> R<Decoded> {
+    let mut buffer = vec![0; sections.iter().map(|s| s.size).max().unwrap_or(0) as usize];
+
+    if let Some(progress) = params.progress {
+        progress.start(sections.iter().map(|s| s.size).sum());
     }
+
+    let mut decoded = Decoded::default();
+    for segment in sections {
+        let left = params
+            .max_instructions
+            .map(|max| max.saturating_sub(decoded.instructions));
+        if left == Some(0) {
+            break;
+        }
+        let data = &mut buffer[..segment.size as usize];
+        file.seek(SeekFrom::Start(segment.offset))
+            .and_then(|_| file.read_exact(data))
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("{e} at offset 0x{:x}", segment.offset))
+            })?;
+        decoded.add(f(
+            data,
+            segment,
+            Params {
+                max_instructions: left,
+                ..params
+            },
+        ));
+        if let Some(progress) = params.progress {
+            progress.advance(segment.size);
+        }
+    }
+
+    if let Some(progress) = params.progress {
+        progress.finish();
+    }
+
+    decoded.sampled = params
+        .max_instructions
+        .is_some_and(|max| decoded.instructions >= max);
+    Ok(decoded)
 }
 
 fn read_file(
-    file: &mut File,
-    sections: &[(u64, u64)],
-    bitness: u32,
-    details: bool,
-) -> R<([bool; CF_COUNT], Option<Vec<Detail>>)> {
-    let mut found = [false; CF_COUNT];
-    let mut details = details.then(|| vec![HashSet::new(); CF_COUNT]);
-    let mut buffer = vec![0; sections.iter().map(|(_, size)| *size).max().unwrap_or(0) as usize];
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    details: Option<&DetailOptions>,
+    output_mode: OutputMode,
+) -> R<Features> {
+    let mut features = Features {
+        found: vec![false; cf_count()],
+        details: details.map(|_| vec![Detail::default(); cf_count()]),
+        total: 0,
+        invalid: 0,
+        vector: 0,
+        opmasks: [0; 8],
+        cpuid: 0,
+        segments: 0,
+        diverse: (0, 0),
+        decoded: Decoded::default(),
+    };
+    let default = DetailOptions::default();
+    let options = details.unwrap_or(&default);
+
+    let mut mismatch = None;
 
-    for &(offset, size) in sections {
-        let data = &mut buffer[..size as usize];
-        file.seek(SeekFrom::Start(offset))?;
-        file.read_exact(data)?;
-        decode(data, bitness, &mut found, details.as_deref_mut());
+    features.decoded = read_sections(file, sections, params, |data, segment, params| {
+        let new = || Tally::new(params.count_by, options, details.is_some());
+        let part = decode_part(data, segment, params, &new, &mut mismatch, output_mode);
+        let decoded = part.decoded();
+        features.add(part, segment);
+        decoded
+    })?;
+
+    if let Some((address, difference)) = mismatch {
+        E!(AppError::ParallelMismatch(address, difference));
     }
 
-    Ok((found, details))
+    Ok(features)
+}
+
+/// Debug name of an iced-x86 enum value, followed by its numeric id if requested.
+fn enum_name(value: impl fmt::Debug, id: usize, style: Style) -> String {
+    match style.with_ids {
+        true => format!("{value:?}#{id}"),
+        _ => format!("{value:?}"),
+    }
 }
 
-fn print_features(found: &[bool], details: Option<&[Detail]>, output_mode: OutputMode) {
+/// Most used mnemonics of the feature, limited by the style, and the number of omitted ones.
+fn top_mnemonics(detail: &Detail, style: Style) -> (Vec<(Mnemonic, u64)>, usize) {
+    let mut mnemonics: Vec<_> = detail.mnemonics.iter().map(|(&m, &c)| (m, c)).collect();
+    mnemonics.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+
+    let shown = style
+        .top_mnemonics
+        .unwrap_or(usize::MAX)
+        .min(mnemonics.len());
+    let more = mnemonics.len() - shown;
+    mnemonics.truncate(shown);
+    (mnemonics, more)
+}
+
+fn print_detail(detail: &Detail, style: Style) {
+    let precision = style.precision;
+    let (mnemonics, more) = top_mnemonics(detail, style);
+
+    // Mnemonic ratios are relative to the feature, not to all instructions.
+    for (m, count) in mnemonics {
+        let ratio = ratio(count, detail.count);
+        println!(
+            "    {}: {count} ({ratio:.precision$}%)",
+            enum_name(m, m as usize, style)
+        );
+    }
+
+    if more > 0 {
+        println!("    (+{more} more)");
+    }
+
+    if detail.segments > 1 {
+        let (address, count) = detail.dominant;
+        let ratio = ratio(count, detail.count);
+        println!("    mostly in segment 0x{address:x} ({ratio:.precision$}%)");
+    }
+
+    if !detail.samples.is_empty() {
+        print!("    at: ");
+        for address in &detail.samples {
+            print!("0x{address:x} ");
+        }
+        println!();
+    }
+}
+
+/// Proportional bar, the longest for the `max` count.
+fn bar(count: u64, max: u64) -> String {
+    const WIDTH: u64 = 40;
+    match max {
+        0 => String::new(),
+        m => "#".repeat((count * WIDTH).div_ceil(m) as usize),
+    }
+}
+
+/// Prints found features grouped by CPUID leaf, sorted by count within a leaf.
+fn print_by_leaf(features: &Features, style: Style) {
+    let details = features.details.as_deref();
+    let count = |f: CpuidFeature| details.map_or(0, |d| d[f as usize].count);
+    let precision = style.precision;
+    let max = CpuidFeature::values().map(count).max().unwrap_or(0);
+
+    for name in leaves::names() {
+        let mut group: Vec<_> = found_features(&features.found)
+            .into_iter()
+            .filter(|&f| leaves::leaf(f) == name && (details.is_none() || count(f) > 0))
+            .collect();
+
+        if group.is_empty() {
+            continue;
+        }
+
+        match style.sort {
+            Sort::Era => group = sorted(group, style),
+            _ => group.sort_by_key(|&f| Reverse(count(f))),
+        }
+
+        println!("{name}:");
+        for feature in group {
+            print!("    {}", enum_name(feature, feature as usize, style));
+            if details.is_some() {
+                let count = count(feature);
+                print!(": {count} ({:.precision$}%)", ratio(count, features.total));
+                if style.bars {
+                    print!(" {}", bar(count, max));
+                }
+            }
+            println!();
+        }
+    }
+}
+
+fn print_features(features: &Features, params: Params, style: Style, output_mode: OutputMode) {
+    let found = &features.found;
+    let details = features.details.as_deref();
+    let total = features.total;
+    let precision = style.precision;
+    let max = details.map_or(0, |d| d.iter().map(|d| d.count).max().unwrap_or(0));
+
     if output_mode > OutputMode::Quiet {
         print!("Features: ");
 
-        if details.is_some() {
+        if details.is_some() || style.by_leaf {
             println!();
         }
     }
 
+    if style.by_leaf {
+        print_by_leaf(features, style);
+    }
+
     macro_rules! body {
         ($($d: expr)?) => {{
-            for feature in CpuidFeature::values() {
-                let index = feature as usize;
-                if let Some(true) = found.get(index) {
-                    print!("{feature:?} ");
-                    $(if let Some(d) = $d.get(index) {
-                        print!(": ");
-                        for m in d {
-                            print!("{m:?} ");
-                        }
-                        println!();
-                    })?
+            for feature in sorted(found_features(found), style) {
+                $(if let Some(d) = $d.get(feature as usize) {
+                    if d.count == 0 {
+                        continue;
+                    }
+                    print!("{}", enum_name(feature, feature as usize, style));
+                    print!(": {} ({:.precision$}%)", d.count, ratio(d.count, total));
+                    if style.bars {
+                        print!(" {}", bar(d.count, max));
+                    }
+                    println!();
+                    print_detail(d, style);
+                    continue;
+                })?
+                let name = enum_name(feature, feature as usize, style);
+                match output_mode > OutputMode::Quiet {
+                    true => print!("{name} "),
+                    // One per line for easy parsing.
+                    _ => println!("{name}"),
                 }
             }
         }};
     }
 
-    match details {
-        Some(d) => body!(d),
+    match (details, style.by_leaf) {
+        (_, true) => {}
+        (Some(d), _) => body!(d),
         _ => body!(),
     }
 
+    if output_mode > OutputMode::Quiet && details.is_none() && !style.by_leaf {
+        println!();
+    }
+
+    if output_mode > OutputMode::Normal {
+        println!("Widest vector: {}", VECTORS[features.vector]);
+    }
+
+    if output_mode > OutputMode::Quiet {
+        print_opmasks(features);
+    }
+
+    // Points deeper analysis at the richest segment.
+    if output_mode > OutputMode::Normal && features.segments > 1 {
+        let (address, count) = features.diverse;
+        println!("Most diverse segment: 0x{address:x} ({count} features)");
+    }
+
+    if let Some(warning) = cpuid_warning(features) {
+        warnings::warn(warning, false);
+        print_dispatched(features, params.baseline, output_mode);
+    }
+
+    if let Some(warning) = amx_warning(features) {
+        warnings::warn(warning, false);
+    }
+}
+
+fn cpuid_warning(features: &Features) -> Option<String> {
+    match features.cpuid {
+        0 => None,
+        n => Some(format!(
+            "CPUID usage detected ({n} instructions). The program can switch instruction sets in runtime."
+        )),
+    }
+}
+
+/// Advanced Matrix Extensions, usable only after the OS enables the tile state on request.
+const AMX: &[CpuidFeature] = &[
+    CpuidFeature::AMX_TILE,
+    CpuidFeature::AMX_INT8,
+    CpuidFeature::AMX_BF16,
+    CpuidFeature::AMX_FP16,
+    CpuidFeature::AMX_COMPLEX,
+];
+
+fn amx_warning(features: &Features) -> Option<String> {
+    let amx: Vec<_> = AMX
+        .iter()
+        .filter(|&&f| features.found[f as usize])
+        .map(|f| format!("{f:?}"))
+        .collect();
+    (!amx.is_empty()).then(|| {
+        format!(
+            "AMX usage detected ({}). Tile state must be enabled by the OS on request (XFD).",
+            amx.join(" ")
+        )
+    })
+}
+
+/// Lists features the program may select in runtime, in verbose mode.
+fn print_dispatched(features: &Features, baseline: &[CpuidFeature], output_mode: OutputMode) {
+    if output_mode <= OutputMode::Normal {
+        return;
+    }
+
+    let dispatched: Vec<_> = found_features(&features.found)
+        .into_iter()
+        .filter(|f| !baseline.contains(f) && *f != CpuidFeature::CPUID)
+        .collect();
+
+    if !dispatched.is_empty() {
+        print!("Note: features possibly selected in runtime: ");
+        for feature in dispatched {
+            print!("{feature:?} ");
+        }
+        println!();
+    }
+}
+
+/// Escapes characters special in Markdown table cells.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn print_row(cells: &[String]) {
+    println!("| {} |", cells.join(" | "));
+}
+
+/// Prints a table header, the last `numeric` columns are right-aligned.
+fn print_header(columns: &[&str], numeric: usize) {
+    print_row(&columns.iter().map(|c| escape(c)).collect::<Vec<_>>());
+    let align: Vec<_> = (0..columns.len())
+        .map(|i| match i + numeric < columns.len() {
+            true => "---".to_string(),
+            _ => "---:".to_string(),
+        })
+        .collect();
+    print_row(&align);
+}
+
+/// Renders found features as GitHub-flavored Markdown tables, with a section per feature in details mode.
+fn print_markdown(features: &Features, params: Params, style: Style, output_mode: OutputMode) {
+    let details = features.details.as_deref();
+    let count = |f: CpuidFeature| details.map_or(0, |d| d[f as usize].count);
+    let precision = style.precision;
+
+    let found: Vec<_> = found_features(&features.found)
+        .into_iter()
+        .filter(|&f| details.is_none() || count(f) > 0)
+        .collect();
+    let mut found = sorted(found, style);
+    if style.by_leaf {
+        found.sort_by_key(|&f| leaves::names().position(|n| n == leaves::leaf(f)));
+    }
+
     if output_mode > OutputMode::Quiet {
-        if details.is_none() {
+        println!("## Features");
+        println!();
+    }
+
+    let mut columns = vec!["Feature"];
+    if style.by_leaf {
+        columns.push("Leaf");
+    }
+    if details.is_some() {
+        columns.extend(["Count", "Ratio"]);
+    }
+    print_header(&columns, columns.len() - 1 - style.by_leaf as usize);
+
+    for &feature in &found {
+        let mut cells = vec![escape(&enum_name(feature, feature as usize, style))];
+        if style.by_leaf {
+            cells.push(escape(leaves::leaf(feature)));
+        }
+        if details.is_some() {
+            let count = count(feature);
+            cells.push(count.to_string());
+            cells.push(format!("{:.precision$}%", ratio(count, features.total)));
+        }
+        print_row(&cells);
+    }
+
+    if let Some(details) = details {
+        for &feature in &found {
+            let detail = &details[feature as usize];
+            let (mnemonics, more) = top_mnemonics(detail, style);
+
+            println!();
+            println!(
+                "### {}",
+                escape(&enum_name(feature, feature as usize, style))
+            );
             println!();
+            print_header(&["Mnemonic", "Count", "Ratio"], 2);
+            for (m, count) in mnemonics {
+                let ratio = ratio(count, detail.count);
+                print_row(&[
+                    escape(&enum_name(m, m as usize, style)),
+                    count.to_string(),
+                    format!("{ratio:.precision$}%"),
+                ]);
+            }
+
+            if more > 0 {
+                println!();
+                println!("+{more} more");
+            }
+
+            if !detail.samples.is_empty() {
+                println!();
+                print!("At:");
+                for address in &detail.samples {
+                    print!(" `0x{address:x}`");
+                }
+                println!();
+            }
         }
+    }
+
+    if output_mode > OutputMode::Quiet {
+        println!();
+        if output_mode > OutputMode::Normal {
+            println!("Widest vector: {}", VECTORS[features.vector]);
+        }
+        print_opmasks(features);
+    }
+
+    if let Some(warning) = cpuid_warning(features) {
+        warnings::warn(warning, false);
+        print_dispatched(features, params.baseline, output_mode);
+    }
+
+    if let Some(warning) = amx_warning(features) {
+        warnings::warn(warning, false);
+    }
+}
+
+/// Orders features as requested by the style, keeping the `CpuidFeature` order within an era.
+fn sorted(mut features: Vec<CpuidFeature>, style: Style) -> Vec<CpuidFeature> {
+    match style.sort {
+        Sort::Name => features.sort_by_cached_key(|f| format!("{f:?}")),
+        Sort::Id => {}
+        Sort::Era => features.sort_by_key(|&f| Reverse(profiles::era(f))),
+    }
+    features
+}
 
-        if let Some(true) = found.get(CpuidFeature::CPUID as usize) {
-            println!("Warning: CPUID usage detected. The program can switch instruction sets in runtime.")
+/// Prints `id,count` rows of the features found in id order, only ids without details.
+/// Counts are always available, the format selects `details` mode.
+fn print_id_csv(features: &Features) {
+    let details = features.details.as_deref().unwrap_or_default();
+    println!("id,count");
+    for feature in found_features(&features.found) {
+        let id = feature as usize;
+        if let Some(detail) = details.get(id).filter(|d| d.count > 0) {
+            println!("{id},{}", detail.count);
         }
     }
 }
 
+fn found_features(found: &[bool]) -> Vec<CpuidFeature> {
+    CpuidFeature::values()
+        .filter(|f| found.get(*f as usize) == Some(&true))
+        .collect()
+}
+
+fn print_diff_line(sign: char, feature: CpuidFeature, old: u64, new: u64, output_mode: OutputMode) {
+    if output_mode > OutputMode::Quiet {
+        print!("    ");
+    }
+    println!("{sign} {feature:?}: {old} -> {new}");
+}
+
+/// Prints features added, removed or changed in `new` compared to `old`.
+/// Returns whether any feature was added.
+pub fn print_diff(old: &[u64], new: &[u64], output_mode: OutputMode) -> bool {
+    let mut gained = false;
+    let mut changed = false;
+
+    if output_mode > OutputMode::Quiet {
+        println!("Changes: ");
+    }
+
+    for feature in CpuidFeature::values() {
+        let index = feature as usize;
+        let (Some(&a), Some(&b)) = (old.get(index), new.get(index)) else {
+            continue;
+        };
+        let sign = match (a, b) {
+            _ if a == b => continue,
+            (0, _) => '+',
+            (_, 0) => '-',
+            _ => '~',
+        };
+        gained |= sign == '+';
+        changed = true;
+        print_diff_line(sign, feature, a, b, output_mode);
+    }
+
+    if !changed && output_mode > OutputMode::Quiet {
+        println!("    None");
+    }
+
+    gained
+}
+
+fn print_symbols(
+    symbols: &[Symbol],
+    found: &[Vec<bool>],
+    baseline: &[CpuidFeature],
+    output_mode: OutputMode,
+) {
+    if output_mode > OutputMode::Quiet {
+        println!("Functions: ");
+    }
+
+    let indent = match output_mode > OutputMode::Quiet {
+        true => "    ",
+        _ => "",
+    };
+
+    for (symbol, found) in symbols.iter().zip(found) {
+        let mut features = CpuidFeature::values()
+            .filter(|f| found.get(*f as usize) == Some(&true) && !baseline.contains(f))
+            .peekable();
+
+        if features.peek().is_none() {
+            continue;
+        }
+
+        print!("{indent}{}: ", symbol.name);
+        for feature in features {
+            print!("{feature:?} ");
+        }
+        println!();
+    }
+}
+
 pub fn run(
-    file: &mut File,
-    sections: &[(u64, u64)],
-    bitness: u32,
-    details: bool,
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    details: Option<&DetailOptions>,
+    style: Style,
+    output_mode: OutputMode,
+) -> R<Summary> {
+    let features = read_file(file, sections, params, details, output_mode)?;
+    match style.format {
+        Format::Markdown => print_markdown(&features, params, style, output_mode),
+        // Printed by the caller, which knows the path.
+        Format::Ndjson => {}
+        Format::IdCsv => print_id_csv(&features),
+        _ => print_features(&features, params, style, output_mode),
+    }
+
+    if features.details.is_some()
+        && params.count_by == CountBy::All
+        && output_mode > OutputMode::Bare
+    {
+        println!("Note: instructions belonging to multiple features are counted in each of them.");
+    }
+
+    Ok(summarize(features))
+}
+
+fn summarize(features: Features) -> Summary {
+    Summary {
+        found: found_features(&features.found),
+        total: features.total,
+        invalid: features.invalid,
+        counts: features
+            .details
+            .map(|d| d.iter().map(|d| d.count).collect()),
+        padding: features.decoded.padding,
+        sampled: features.decoded.sampled,
+    }
+}
+
+/// Prints the most used mnemonic of each feature.
+pub fn run_top(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    options: &DetailOptions,
+    style: Style,
+    output_mode: OutputMode,
+) -> R<Summary> {
+    let features = read_file(file, sections, params, Some(options), output_mode)?;
+    let details = features.details.as_deref().unwrap_or_default();
+
+    if output_mode > OutputMode::Quiet {
+        println!("Features: ");
+    }
+
+    let indent = match output_mode > OutputMode::Quiet {
+        true => "    ",
+        _ => "",
+    };
+
+    for feature in sorted(found_features(&features.found), style) {
+        let Some(detail) = details.get(feature as usize) else {
+            continue;
+        };
+        // Ties are resolved the same way as in details mode.
+        let top = detail
+            .mnemonics
+            .iter()
+            .max_by(|(a, x), (b, y)| x.cmp(y).then(b.cmp(a)));
+        if let Some((mnemonic, count)) = top {
+            println!(
+                "{indent}{}: {} ({count} of {})",
+                enum_name(feature, feature as usize, style),
+                enum_name(mnemonic, *mnemonic as usize, style),
+                detail.count
+            );
+        }
+    }
+
+    Ok(summarize(features))
+}
+
+pub fn run_symbols(
+    file: &mut impl Source,
+    sections: &[Segment],
+    symbols: &[Symbol],
+    params: Params,
+    output_mode: OutputMode,
+) -> R<()> {
+    let mut found = vec![vec![false; cf_count()]; symbols.len()];
+
+    read_sections(file, sections, params, |data, segment, params| {
+        decode_symbols(
+            data,
+            segment,
+            params,
+            symbols,
+            output_mode,
+            |index, instruction| {
+                for &feature in instruction.cpuid_features() {
+                    if let Some(flag) = found[index].get_mut(feature as usize) {
+                        *flag = true;
+                    }
+                }
+            },
+        )
+    })?;
+
+    print_symbols(symbols, &found, params.baseline, output_mode);
+    Ok(())
+}
+
+pub fn run_used_by(
+    file: &mut impl Source,
+    sections: &[Segment],
+    symbols: &[Symbol],
+    params: Params,
+    feature: CpuidFeature,
     output_mode: OutputMode,
 ) -> R<()> {
-    let (found, details) = read_file(file, sections, bitness, details)?;
-    print_features(&found, details.as_deref(), output_mode);
+    let mut counts = vec![0u64; symbols.len()];
+
+    read_sections(file, sections, params, |data, segment, params| {
+        decode_symbols(
+            data,
+            segment,
+            params,
+            symbols,
+            output_mode,
+            |index, instruction| {
+                if instruction.cpuid_features().contains(&feature) {
+                    counts[index] += 1;
+                }
+            },
+        )
+    })?;
+
+    let mut used: Vec<_> = symbols
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    used.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+    if output_mode > OutputMode::Quiet {
+        println!("Functions using {feature:?}: {}", used.len());
+    }
+
+    for (symbol, count) in used {
+        match output_mode > OutputMode::Quiet {
+            true => println!("    {}: {count}", symbol.name),
+            _ => println!("{}: {count}", symbol.name),
+        }
+    }
+
     Ok(())
 }
+
+pub fn run_disasm(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    filter: Option<CpuidFeature>,
+    features: bool,
+    output_mode: OutputMode,
+) -> R<Decoded> {
+    let mut formatter = IntelFormatter::new();
+    let mut text = String::new();
+
+    read_sections(file, sections, params, |data, segment, params| {
+        decode_segment(data, segment, params, output_mode, |instruction| {
+            if let Some(feature) = filter {
+                if !instruction.cpuid_features().contains(&feature) {
+                    return;
+                }
+            }
+
+            let start = (instruction.ip() - segment.address) as usize;
+            let bytes = &data[start..start + instruction.len()];
+
+            text.clear();
+            formatter.format(instruction, &mut text);
+
+            print!("{:016x}  ", instruction.ip());
+            for b in bytes {
+                print!("{b:02x}");
+            }
+            print!("{:w$}  {text}", "", w = 30 - bytes.len() * 2);
+            if features {
+                print!("{:w$}  ;", "", w = 40usize.saturating_sub(text.len()));
+                for feature in instruction.cpuid_features() {
+                    print!(" {feature:?}");
+                }
+            }
+            println!();
+        })
+    })
+}
+
+/// Calls `f` for every instruction of the segment along with its bytes.
+fn visit_segment(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    output_mode: OutputMode,
+    mut f: impl FnMut(&Instruction, &[u8]),
+) -> Decoded {
+    decode_segment(data, segment, params, output_mode, |instruction| {
+        let start = (instruction.ip() - segment.address) as usize;
+        f(instruction, &data[start..start + instruction.len()]);
+    })
+}
+
+/// Calls `f` for every decoded instruction along with its bytes.
+pub fn visit(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    output_mode: OutputMode,
+    mut f: impl FnMut(&Instruction, &[u8]),
+) -> R<Decoded> {
+    read_sections(file, sections, params, |data, segment, params| {
+        visit_segment(data, segment, params, output_mode, &mut f)
+    })
+}
+
+/// Order-independent hash of decoded instructions, equal for equally decoded code.
+fn fingerprint(instruction: &Instruction) -> u64 {
+    (instruction.ip() ^ (instruction.code() as u64) << 48).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// Results collected over decoded instructions, combinable across chunks of a segment.
+trait Collect: Send {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]);
+    fn merge(&mut self, other: Self);
+}
+
+impl<T: Task + Send> Collect for T {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]) {
+        Task::add(self, instruction, bytes)
+    }
+
+    fn merge(&mut self, other: Self) {
+        Task::merge(self, other)
+    }
+}
+
+/// Per-instruction part of `Features` in a segment, besides the totals of `Part`.
+#[derive(Debug, PartialEq)]
+struct Tally<'a> {
+    count_by: CountBy,
+    options: &'a DetailOptions,
+    details: Option<Vec<Detail>>,
+    vector: usize,
+    opmasks: [u64; 8],
+    cpuid: u64,
+}
+
+impl<'a> Tally<'a> {
+    fn new(count_by: CountBy, options: &'a DetailOptions, details: bool) -> Self {
+        Tally {
+            count_by,
+            options,
+            details: details.then(|| vec![Detail::default(); cf_count()]),
+            vector: 0,
+            opmasks: [0; 8],
+            cpuid: 0,
+        }
+    }
+}
+
+impl Collect for Tally<'_> {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.vector = vector_width(instruction).max(self.vector);
+        if let Some(count) = self.opmasks.get_mut(opmask(instruction)) {
+            *count += 1;
+        }
+        self.cpuid += (instruction.mnemonic() == Mnemonic::Cpuid) as u64;
+
+        let Some(details) = &mut self.details else {
+            return;
+        };
+        let options = self.options;
+        let mnemonic = instruction.mnemonic();
+        if !options.mnemonics.is_empty() && !options.mnemonics.contains(&mnemonic) {
+            return;
+        }
+        for &feature in counted_features(instruction, self.count_by) {
+            if let Some(d) = details.get_mut(feature as usize) {
+                d.count += 1;
+                *d.mnemonics.entry(mnemonic).or_default() += 1;
+                if d.samples.len() < options.samples {
+                    d.samples.push(instruction.ip());
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.vector = self.vector.max(other.vector);
+        for (count, other) in self.opmasks.iter_mut().zip(other.opmasks) {
+            *count += other;
+        }
+        self.cpuid += other.cpuid;
+        if let (Some(details), Some(other)) = (&mut self.details, other.details) {
+            merge_details(details, other, self.options.samples);
+        }
+    }
+}
+
+/// Adds counts of `other` to `details`, keeping the first samples.
+fn merge_details(details: &mut [Detail], other: Vec<Detail>, samples: usize) {
+    for (d, other) in details.iter_mut().zip(other) {
+        d.count += other.count;
+        for (mnemonic, count) in other.mnemonics {
+            *d.mnemonics.entry(mnemonic).or_default() += count;
+        }
+        d.samples.extend(other.samples);
+        d.samples.truncate(samples);
+    }
+}
+
+/// Task results over a part of the code, along with the totals.
+struct Part<T> {
+    task: T,
+    found: Vec<bool>,
+    total: u64,
+    invalid: u64,
+    /// Sum of instruction fingerprints.
+    checksum: u64,
+    /// Padding instructions skipped.
+    padding: u64,
+}
+
+impl<T: Collect> Part<T> {
+    fn new(task: T) -> Self {
+        Part {
+            task,
+            found: vec![false; cf_count()],
+            total: 0,
+            invalid: 0,
+            checksum: 0,
+            padding: 0,
+        }
+    }
+
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]) {
+        self.task.add(instruction, bytes);
+        self.total += 1;
+        self.checksum = self.checksum.wrapping_add(fingerprint(instruction));
+        if instruction.is_invalid() {
+            self.invalid += 1;
+        }
+        for &feature in instruction.cpuid_features() {
+            if let Some(flag) = self.found.get_mut(feature as usize) {
+                *flag = true;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.task.merge(other.task);
+        for (flag, other) in self.found.iter_mut().zip(other.found) {
+            *flag |= other;
+        }
+        self.total += other.total;
+        self.invalid += other.invalid;
+        self.checksum = self.checksum.wrapping_add(other.checksum);
+        self.padding += other.padding;
+    }
+
+    fn decoded(&self) -> Decoded {
+        Decoded {
+            instructions: self.total,
+            padding: self.padding,
+            sampled: false,
+        }
+    }
+
+    /// The first of the totals, decoded instructions and task results of this sequentially
+    /// decoded part differing from the `other` part decoded in parallel.
+    fn difference(&self, other: &Self) -> Option<String>
+    where
+        T: PartialEq + fmt::Debug,
+    {
+        let totals = [
+            ("total", self.total, other.total),
+            ("invalid", self.invalid, other.invalid),
+            ("checksum", self.checksum, other.checksum),
+            ("padding", self.padding, other.padding),
+        ];
+        if let Some((name, a, b)) = totals.into_iter().find(|(_, a, b)| a != b) {
+            return Some(format!("{name} {a} sequentially, {b} in parallel"));
+        }
+        let found = |part: &Self, f: CpuidFeature| part.found.get(f as usize) == Some(&true);
+        if let Some(f) = CpuidFeature::values().find(|&f| found(self, f) != found(other, f)) {
+            let how = match found(self, f) {
+                true => "sequentially",
+                _ => "in parallel",
+            };
+            return Some(format!("feature {f:?} found only {how}"));
+        }
+        (self.task != other.task).then(|| debug_difference(&self.task, &other.task))
+    }
+}
+
+/// The first line differing in debug output of the sequential and parallel results,
+/// along with the field it belongs to.
+fn debug_difference(a: &impl fmt::Debug, b: &impl fmt::Debug) -> String {
+    let (a, b) = (format!("{a:#?}"), format!("{b:#?}"));
+    let mut field = "";
+    for (x, y) in a.lines().zip(b.lines()) {
+        // Fields of the value itself are indented once.
+        let name = x
+            .strip_prefix("    ")
+            .filter(|x| !x.starts_with(' '))
+            .and_then(|x| x.split_once(':'));
+        if let Some((name, _)) = name {
+            field = name;
+        }
+        if x != y {
+            let value = |line: &str| {
+                let line = line.trim().trim_end_matches(',');
+                line.strip_prefix(field)
+                    .and_then(|l| l.strip_prefix(": "))
+                    .unwrap_or(line)
+                    .to_string()
+            };
+            return format!(
+                "task {field}: {} sequentially, {} in parallel",
+                value(x),
+                value(y)
+            );
+        }
+    }
+    "task results".into()
+}
+
+/// Smallest chunk of a segment worth a separate thread.
+const MIN_CHUNK: usize = 1 << 20;
+
+/// Bytes decoded before a chunk to find instruction boundaries, x86 code realigns quickly.
+const OVERLAP: usize = 64;
+
+/// Instructions starting within a range of the segment.
+struct Chunk<T> {
+    part: Part<T>,
+    /// Offset of the first instruction counted.
+    first: Option<usize>,
+    /// Offset past the last instruction counted.
+    next: usize,
+    /// Address of an instruction cut off by the end of the segment.
+    cut_off: Option<u64>,
+}
+
+/// Decodes from offset `from`, counting only instructions starting within `range`.
+fn decode_chunk<T: Collect>(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    new: &impl Fn() -> T,
+    from: usize,
+    range: Range<usize>,
+) -> Chunk<T> {
+    let ip = segment.address + from as u64;
+    let bitness = segment.bitness.unwrap_or(params.bitness);
+    let mut decoder = Decoder::with_ip(bitness, &data[from..], ip, params.options);
+    let mut instruction = Instruction::default();
+    let mut chunk = Chunk {
+        part: Part::new(new()),
+        first: None,
+        next: range.start,
+        cut_off: None,
+    };
+
+    while decoder.can_decode() {
+        decoder.decode_out(&mut instruction);
+
+        if decoder.last_error() == DecoderError::NoMoreBytes {
+            chunk.cut_off = Some(instruction.ip());
+            break;
+        }
+
+        let start = (instruction.ip() - segment.address) as usize;
+        if start >= range.end {
+            break;
+        }
+        if start < range.start {
+            continue;
+        }
+        chunk.first.get_or_insert(start);
+        chunk.next = start + instruction.len();
+        if is_padding(&instruction, params) {
+            chunk.part.padding += 1;
+            continue;
+        }
+        if params.excluded.contains(&instruction.mnemonic()) {
+            continue;
+        }
+        chunk.part.add(&instruction, &data[start..chunk.next]);
+    }
+
+    chunk
+}
+
+/// Decodes a large segment in chunks, each in its own thread.
+/// A chunk not starting where the previous one ended is out of sync and gets decoded again,
+/// so the result matches sequential decoding.
+fn decode_parallel<T: Collect>(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    new: &(impl Fn() -> T + Sync),
+    output_mode: OutputMode,
+) -> Part<T> {
+    let data = match params.trim_padding {
+        true => trim_padding(data, segment, output_mode),
+        _ => data,
+    };
+    let jobs = params.jobs.min(data.len() / MIN_CHUNK).max(1);
+    let size = data.len().div_ceil(jobs);
+    let ranges: Vec<_> = (0..jobs)
+        .map(|i| i * size..((i + 1) * size).min(data.len()))
+        .collect();
+
+    let chunks: Vec<Chunk<T>> = thread::scope(|s| {
+        let handles: Vec<_> = ranges
+            .iter()
+            .map(|range| {
+                let from = range.start.saturating_sub(OVERLAP);
+                let range = range.clone();
+                s.spawn(move || decode_chunk(data, segment, params, new, from, range))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+            .collect()
+    });
+
+    let verbose = output_mode > OutputMode::Normal;
+    let mut part = Part::new(new());
+    let mut next = 0;
+    let mut cut_off = None;
+
+    for (chunk, range) in chunks.into_iter().zip(ranges) {
+        let chunk = match chunk.first == Some(next) {
+            true => chunk,
+            _ => {
+                if verbose {
+                    println!(
+                        "Note: chunk at 0x{:x} out of sync, decoding again",
+                        segment.address + range.start as u64
+                    );
+                }
+                decode_chunk(data, segment, params, new, next, next..range.end.max(next))
+            }
+        };
+        next = chunk.next;
+        cut_off = chunk.cut_off;
+        part.merge(chunk.part);
+    }
+
+    if let Some(ip) = cut_off {
+        cut_off_warning(ip, segment);
+    }
+    if verbose {
+        println!(
+            "Segment 0x{:x}: {} instructions, decoded in {jobs} chunks",
+            segment.address, part.total
+        );
+    }
+
+    part
+}
+
+/// Difference of the parallel decoding result from decoding the segment sequentially, if any.
+fn verify_parallel<T: Collect + PartialEq + fmt::Debug>(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    new: &impl Fn() -> T,
+    part: &Part<T>,
+    output_mode: OutputMode,
+) -> Option<String> {
+    let data = match params.trim_padding {
+        true => trim_padding(data, segment, OutputMode::Quiet),
+        _ => data,
+    };
+    let chunk = decode_chunk(data, segment, params, new, 0, 0..data.len());
+    let difference = chunk.part.difference(part);
+    if difference.is_none() && output_mode > OutputMode::Normal {
+        println!(
+            "Segment 0x{:x}: parallel decoding matches sequential",
+            segment.address
+        );
+    }
+    difference
+}
+
+/// Decodes the segment into a part, with results collected by tasks made with `new`.
+/// Segments large enough are split between `params.jobs` threads, and decoded once more
+/// sequentially with `params.verify_parallel`, keeping the first difference in `mismatch`.
+fn decode_part<T: Collect + PartialEq + fmt::Debug>(
+    data: &[u8],
+    segment: &Segment,
+    params: Params,
+    new: &(impl Fn() -> T + Sync),
+    mismatch: &mut Option<(u64, String)>,
+    output_mode: OutputMode,
+) -> Part<T> {
+    // A sample must be the first instructions, so it is always decoded sequentially.
+    let parallel = params.max_instructions.is_none() && params.jobs > 1;
+    match parallel && data.len() >= MIN_CHUNK * 2 {
+        true => {
+            let part = decode_parallel(data, segment, params, new, output_mode);
+            if params.verify_parallel && mismatch.is_none() {
+                *mismatch = verify_parallel(data, segment, params, new, &part, output_mode)
+                    .map(|difference| (segment.address, difference));
+            }
+            part
+        }
+        _ => {
+            let mut part = Part::new(new());
+            let decoded =
+                visit_segment(data, segment, params, output_mode, |instruction, bytes| {
+                    part.add(instruction, bytes)
+                });
+            part.padding = decoded.padding;
+            part
+        }
+    }
+}
+
+/// Counts instructions without a task, the fastest analysis, decoding sequentially.
+/// Features are only looked at if `features` is set, or to count the ones beyond `baseline`.
+pub fn run_count(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    features: bool,
+    baseline: Option<&[CpuidFeature]>,
+    precision: usize,
+    output_mode: OutputMode,
+) -> R<Summary> {
+    let features = features || baseline.is_some();
+    let mut found = vec![false; cf_count()];
+    let (mut total, mut invalid, mut extended) = (0, 0, 0);
+
+    let decoded = read_sections(file, sections, params, |data, segment, params| {
+        decode_segment(data, segment, params, output_mode, |instruction| {
+            total += 1;
+            invalid += instruction.is_invalid() as u64;
+            if !features {
+                return;
+            }
+            let cpuid = instruction.cpuid_features();
+            for &feature in cpuid {
+                if let Some(flag) = found.get_mut(feature as usize) {
+                    *flag = true;
+                }
+            }
+            if let Some(baseline) = baseline {
+                extended += cpuid.iter().any(|f| !baseline.contains(f)) as u64;
+            }
+        })
+    })?;
+
+    if output_mode > OutputMode::Quiet {
+        print!("Instructions: ");
+    }
+    println!("{total}");
+
+    // Quiet output stays a single number for scripts.
+    if baseline.is_some() && output_mode > OutputMode::Quiet {
+        let ratio = ratio(extended, total);
+        println!("Beyond baseline: {extended} ({ratio:.precision$}%)");
+    }
+
+    Ok(Summary {
+        found: found_features(&found),
+        total,
+        invalid,
+        counts: None,
+        padding: decoded.padding,
+        sampled: decoded.sampled,
+    })
+}
+
+/// Runs a custom analysis task over every decoded instruction.
+/// Each segment is analyzed separately and merged into the `task`.
+/// Segments large enough are split between `params.jobs` threads.
+pub fn run_task<T: Task + Default + Send + PartialEq + fmt::Debug>(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    task: &mut T,
+    precision: usize,
+    output_mode: OutputMode,
+) -> R<Summary> {
+    let mut total = Part::new(T::default());
+    let mut mismatch = None;
+
+    let decoded = read_sections(file, sections, params, |data, segment, params| {
+        let part = decode_part(
+            data,
+            segment,
+            params,
+            &T::default,
+            &mut mismatch,
+            output_mode,
+        );
+        let decoded = part.decoded();
+        total.merge(part);
+        decoded
+    })?;
+
+    if let Some((address, difference)) = mismatch {
+        E!(AppError::ParallelMismatch(address, difference));
+    }
+
+    Task::merge(task, total.task);
+    task.print(output_mode, precision);
+    Ok(Summary {
+        found: found_features(&total.found),
+        total: total.total,
+        invalid: total.invalid,
+        counts: None,
+        padding: decoded.padding,
+        sampled: decoded.sampled,
+    })
+}
+
+/// Prints the run totals as a single `key=value` line for scripts.
+pub fn print_summary(summary: &Summary) {
+    println!(
+        "instructions={} features={} invalid={} level={} warnings={}",
+        summary.total,
+        summary.found.len(),
+        summary.invalid,
+        profiles::level(&summary.found),
+        warnings::count(),
+    );
+}
+
+fn json_string(text: &str) -> String {
+    let mut s = String::with_capacity(text.len() + 2);
+    s.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            c if c.is_control() => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// Indents compact JSON by two spaces per level, keeping empty objects and arrays inline.
+fn pretty_json(json: &str) -> String {
+    fn newline(out: &mut String, depth: usize) {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    }
+
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut depth = 0;
+    let (mut in_string, mut escaped) = (false, false);
+    let mut chars = json.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match (escaped, c) {
+                (true, _) => escaped = false,
+                (_, '\\') => escaped = true,
+                (_, '"') => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                out.push(c);
+                if let Some(end) = chars.next_if(|&e| e == '}' || e == ']') {
+                    out.push(end);
+                    continue;
+                }
+                depth += 1;
+                newline(&mut out, depth);
+            }
+            '}' | ']' => {
+                depth -= 1;
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Prints the run as a single-line JSON object, features are listed with counts in details mode.
+/// Stdout is line buffered, so each object reaches consumers as soon as it is complete.
+/// With `Style::json_pretty` the object is indented over multiple lines instead.
+pub fn print_ndjson(path: &str, summary: &Summary, warnings: &[String], style: Style) {
+    // Always sorted by name, so equal runs give identical lines regardless of `--sort`.
+    let mut found = summary.found.clone();
+    found.sort_by_cached_key(|f| format!("{f:?}"));
+
+    let names = found.iter().map(|f| json_string(&format!("{f:?}")));
+    let features = match &summary.counts {
+        Some(counts) => {
+            let fields: Vec<_> = names
+                .zip(&found)
+                .map(|(name, &f)| (name, counts[f as usize]))
+                .filter(|&(_, count)| count > 0)
+                .map(|(name, count)| format!("{name}:{count}"))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        _ => format!("[{}]", names.collect::<Vec<_>>().join(",")),
+    };
+
+    // Ids let consumers match the enum without relying on names.
+    let ids = match style.with_ids {
+        true => {
+            let fields: Vec<_> = found
+                .iter()
+                .map(|&f| format!("{}:{}", json_string(&format!("{f:?}")), f as usize))
+                .collect();
+            format!(",\"feature_ids\":{{{}}}", fields.join(","))
+        }
+        _ => String::new(),
+    };
+
+    let warnings: Vec<_> = warnings.iter().map(|w| json_string(w)).collect();
+
+    let json = format!(
+        "{{\"path\":{},\"instructions\":{},\"invalid\":{},\"level\":{},\"features\":{features}{ids},\"warnings\":[{}]}}",
+        json_string(path),
+        summary.total,
+        summary.invalid,
+        json_string(profiles::level(&summary.found)),
+        warnings.join(","),
+    );
+    match style.json_pretty {
+        true => println!("{}", pretty_json(&json)),
+        _ => println!("{json}"),
+    }
+}
+
+/// Counts instructions per feature.
+pub fn count(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    output_mode: OutputMode,
+) -> R<Vec<u64>> {
+    let mut counts = vec![0; cf_count()];
+    visit(file, sections, params, output_mode, |instruction, _| {
+        for &feature in counted_features(instruction, params.count_by) {
+            if let Some(count) = counts.get_mut(feature as usize) {
+                *count += 1;
+            }
+        }
+    })?;
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Several MiB of instructions of various lengths mixed with random bytes.
+    fn code() -> Vec<u8> {
+        const INSTRUCTIONS: &[&[u8]] = &[
+            &[0xc5, 0xfd, 0xfe, 0xc1],             // vpaddd ymm0, ymm0, ymm1
+            &[0x66, 0x0f, 0xfe, 0xc1],             // paddd xmm0, xmm1
+            &[0x62, 0xf1, 0x7d, 0x49, 0xfe, 0xc1], // vpaddd zmm0{k1}, zmm0, zmm1
+            &[0x55],                               // push rbp
+            &[0x48, 0x89, 0xe5],                   // mov rbp, rsp
+            &[0x0f, 0xa2],                         // cpuid
+            &[0xe8, 0, 0, 0, 0],                   // call
+            &[0xc3],                               // ret
+            &[0xcc],                               // int3
+        ];
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut code = Vec::new();
+        while code.len() < 5 << 20 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let r = (state >> 33) as usize;
+            match r % 8 {
+                0 => code.push(r as u8),
+                _ => code.extend_from_slice(INSTRUCTIONS[r % INSTRUCTIONS.len()]),
+            }
+        }
+        code
+    }
+
+    fn segment(code: &[u8]) -> Segment {
+        Segment {
+            offset: 0,
+            size: code.len() as u64,
+            address: 0x1000,
+            bitness: None,
+        }
+    }
+
+    fn assert_matches<T: Collect + PartialEq + fmt::Debug>(new: &(impl Fn() -> T + Sync)) {
+        let code = code();
+        let segment = segment(&code);
+        let params = Params {
+            jobs: 4,
+            ..Default::default()
+        };
+        let part = decode_parallel(&code, &segment, params, new, OutputMode::Quiet);
+        let chunk = decode_chunk(&code, &segment, params, new, 0, 0..code.len());
+        assert!(part.total > 0);
+        assert_eq!(chunk.part.difference(&part), None);
+    }
+
+    #[test]
+    fn parallel_details_match_sequential() {
+        let options = DetailOptions {
+            samples: 3,
+            ..Default::default()
+        };
+        assert_matches(&|| Tally::new(CountBy::All, &options, true));
+    }
+
+    #[test]
+    fn parallel_task_matches_sequential() {
+        assert_matches(&crate::tasks::Branches::default);
+    }
+}
@@ -1,4 +1,6 @@
-use std::{error::Error, fmt};
+use iced_x86::CpuidFeature;
+use object::{Architecture, BinaryFormat};
+use std::{error::Error, fmt, io};
 
 pub type R<T> = Result<T, Box<dyn Error>>;
 
@@ -12,19 +14,103 @@ macro_rules! E {
 #[derive(Debug)]
 pub enum AppError {
     WrongTarget,
+    IsDirectory(String),
+    NotFound(String),
+    BrokenLink(String),
+    Symlink(String),
+    NoProcess(u32),
+    NoAccess(u32),
+    Gzip(io::Error),
+    Io(String, io::Error),
+    NotBinary(String, Option<object::Error>),
+    WrongFormat(BinaryFormat),
     WrongArch,
+    ArchNotFound(Architecture),
+    WrongBitness(u32, u32),
     NoText,
+    NoCodeAt(u64),
+    NoSymbols,
+    SymbolNotFound(String),
+    Forbidden(Vec<CpuidFeature>),
+    Incompatible(&'static str, Vec<CpuidFeature>),
+    CheckFailed,
+    ParallelMismatch(u64, String),
 }
 
 impl Error for AppError {}
 
+impl AppError {
+    /// Process exit code of the error category.
+    pub fn code(&self) -> u8 {
+        use AppError::*;
+        match self {
+            WrongTarget | IsDirectory(_) | NotFound(_) | BrokenLink(_) | Symlink(_)
+            | NoProcess(_) | NoAccess(_) => 2,
+            WrongArch | ArchNotFound(_) | WrongBitness(..) => 3,
+            NoText | NoCodeAt(_) => 4,
+            NoSymbols | SymbolNotFound(_) => 5,
+            Forbidden(_) | Incompatible(..) | CheckFailed => 6,
+            Gzip(_) | Io(..) | NotBinary(..) | WrongFormat(_) | ParallelMismatch(..) => 1,
+        }
+    }
+}
+
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use AppError::*;
         match self {
             WrongTarget => write!(f, "Should target a file"),
+            IsDirectory(path) => write!(f, "'{path}' is a directory, should target a file"),
+            NotFound(path) => write!(f, "'{path}' not found"),
+            BrokenLink(path) => write!(f, "'{path}' is a broken symbolic link"),
+            Symlink(path) => write!(f, "'{path}' is a symbolic link, not following"),
+            NoProcess(pid) => write!(f, "Process {pid} not found"),
+            NoAccess(pid) => write!(
+                f,
+                "Cannot read memory of process {pid}, ptrace access is required (try as root)"
+            ),
+            Gzip(e) => write!(f, "Failed to decompress gzip data: {e}"),
+            Io(path, e) => write!(f, "{e} reading '{path}'"),
+            NotBinary(path, None) => {
+                write!(
+                    f,
+                    "'{path}' is not a recognized ELF, PE, COFF or Mach-O binary"
+                )
+            }
+            NotBinary(path, Some(e)) => write!(
+                f,
+                "'{path}' is not a recognized ELF, PE, COFF or Mach-O binary: {e}"
+            ),
+            WrongFormat(format) => write!(f, "File is not a valid {format:?} binary"),
             WrongArch => write!(f, "Unsupported architecture"),
+            ArchNotFound(arch) => write!(f, "Architecture {arch:?} not found in the file"),
+            WrongBitness(requested, detected) => write!(
+                f,
+                "Requested {requested}-bit decoding, but the file is {detected}-bit"
+            ),
             NoText => write!(f, "No 'text' sections found in the file"),
+            NoCodeAt(offset) => write!(f, "No code to decode at offset 0x{offset:x} or after"),
+            NoSymbols => write!(f, "Function symbols are required, but the file is stripped"),
+            SymbolNotFound(name) => write!(f, "No function symbol matching '{name}' found"),
+            Forbidden(features) => {
+                write!(f, "Forbidden features used:")?;
+                for feature in features {
+                    write!(f, " {feature:?}")?;
+                }
+                Ok(())
+            }
+            Incompatible(name, features) => {
+                write!(f, "Features not supported by {name}:")?;
+                for feature in features {
+                    write!(f, " {feature:?}")?;
+                }
+                Ok(())
+            }
+            CheckFailed => write!(f, "Check failed"),
+            ParallelMismatch(address, difference) => write!(
+                f,
+                "Parallel decoding of segment 0x{address:x} does not match sequential decoding, {difference}"
+            ),
         }
     }
 }
@@ -32,6 +118,14 @@ impl fmt::Display for AppError {
 #[derive(Debug)]
 pub enum ArgError {
     Unknown(String),
+    NoValue(String),
+    InvalidValue(String, String),
+    Conflict(String, String),
+    /// Option not available on this platform.
+    #[cfg(not(target_os = "linux"))]
+    Unsupported(String),
+    /// Config file path, line number and the reason it is invalid.
+    Config(String, usize, &'static str),
 }
 
 impl Error for ArgError {}
@@ -41,6 +135,12 @@ impl fmt::Display for ArgError {
         use ArgError::*;
         match self {
             Unknown(arg) => write!(f, "unknown option '{arg}'"),
+            NoValue(arg) => write!(f, "option '{arg}' requires a value"),
+            InvalidValue(arg, value) => write!(f, "invalid value '{value}' for option '{arg}'"),
+            Conflict(arg, other) => write!(f, "option '{arg}' cannot be used with '{other}'"),
+            #[cfg(not(target_os = "linux"))]
+            Unsupported(arg) => write!(f, "option '{arg}' is not supported on this platform"),
+            Config(path, line, reason) => write!(f, "{reason} at line {line} of '{path}'"),
         }
     }
 }
@@ -0,0 +1,429 @@
+use iced_x86::CpuidFeature::{self, *};
+
+use crate::{cli::OutputMode, leaves};
+
+/// Full name, purpose and first microarchitectures of common features.
+const DESCRIPTIONS: &[(CpuidFeature, &str, &str, &str)] = &[
+    (
+        INTEL8086,
+        "Intel 8086",
+        "base 16-bit instruction set",
+        "Intel 8086",
+    ),
+    (
+        INTEL386,
+        "Intel 80386",
+        "32-bit instructions and protected mode",
+        "Intel 80386",
+    ),
+    (
+        X64,
+        "x86-64 (AMD64, Intel 64)",
+        "64-bit long mode instructions",
+        "AMD K8, Intel Prescott",
+    ),
+    (
+        CMOV,
+        "Conditional Move",
+        "branchless conditional register moves",
+        "Intel Pentium Pro",
+    ),
+    (
+        CX8,
+        "CMPXCHG8B",
+        "atomic 8-byte compare and exchange",
+        "Intel Pentium",
+    ),
+    (
+        CMPXCHG16B,
+        "CMPXCHG16B",
+        "atomic 16-byte compare and exchange, used by lock-free algorithms",
+        "Intel Core 2, AMD K8 (later revisions)",
+    ),
+    (
+        FPU,
+        "x87 Floating Point Unit",
+        "legacy scalar floating point on a register stack",
+        "Intel 8087",
+    ),
+    (
+        MMX,
+        "MultiMedia eXtensions",
+        "legacy 64-bit integer SIMD, superseded by SSE2",
+        "Intel Pentium MMX",
+    ),
+    (
+        SSE,
+        "Streaming SIMD Extensions",
+        "128-bit single precision floating point SIMD",
+        "Intel Pentium III",
+    ),
+    (
+        SSE2,
+        "Streaming SIMD Extensions 2",
+        "128-bit double precision and integer SIMD, baseline of x86-64",
+        "Intel Pentium 4, AMD K8",
+    ),
+    (
+        SSE3,
+        "Streaming SIMD Extensions 3",
+        "horizontal arithmetic and unaligned loads",
+        "Intel Prescott, AMD K8 (Venice)",
+    ),
+    (
+        SSSE3,
+        "Supplemental Streaming SIMD Extensions 3",
+        "byte shuffles, absolute values and horizontal integer arithmetic",
+        "Intel Core 2, AMD Bobcat",
+    ),
+    (
+        SSE4_1,
+        "Streaming SIMD Extensions 4.1",
+        "blends, rounding, dot products and wider integer operations",
+        "Intel Penryn, AMD Bulldozer",
+    ),
+    (
+        SSE4_2,
+        "Streaming SIMD Extensions 4.2",
+        "string comparison and CRC32 instructions",
+        "Intel Nehalem, AMD Bulldozer",
+    ),
+    (
+        SSE4A,
+        "Streaming SIMD Extensions 4a",
+        "AMD-only bit field extraction and streaming stores",
+        "AMD K10",
+    ),
+    (
+        POPCNT,
+        "Population Count",
+        "counting set bits of a register",
+        "Intel Nehalem, AMD K10",
+    ),
+    (
+        LZCNT,
+        "Leading Zero Count",
+        "counting leading zero bits (ABM on AMD)",
+        "AMD K10, Intel Haswell",
+    ),
+    (
+        AES,
+        "AES New Instructions (AES-NI)",
+        "hardware accelerated AES encryption rounds",
+        "Intel Westmere, AMD Bulldozer",
+    ),
+    (
+        PCLMULQDQ,
+        "Carry-Less Multiplication",
+        "polynomial multiplication for GCM and CRC computation",
+        "Intel Westmere, AMD Bulldozer",
+    ),
+    (
+        AVX,
+        "Advanced Vector Extensions",
+        "256-bit floating point SIMD and VEX encoding",
+        "Intel Sandy Bridge, AMD Bulldozer",
+    ),
+    (
+        F16C,
+        "16-bit Floating Point Conversion",
+        "conversion between half and single precision",
+        "Intel Ivy Bridge, AMD Piledriver",
+    ),
+    (
+        FMA,
+        "Fused Multiply-Add (FMA3)",
+        "multiply and add with a single rounding",
+        "Intel Haswell, AMD Piledriver",
+    ),
+    (
+        FMA4,
+        "Fused Multiply-Add (FMA4)",
+        "AMD-only four operand fused multiply-add",
+        "AMD Bulldozer (dropped in Zen)",
+    ),
+    (
+        XOP,
+        "eXtended Operations",
+        "AMD-only integer SIMD extensions",
+        "AMD Bulldozer (dropped in Zen)",
+    ),
+    (
+        AVX2,
+        "Advanced Vector Extensions 2",
+        "256-bit integer SIMD, gathers and permutations",
+        "Intel Haswell, AMD Excavator",
+    ),
+    (
+        BMI1,
+        "Bit Manipulation Instructions 1",
+        "bit field extraction, isolation and trailing zero count",
+        "Intel Haswell, AMD Piledriver",
+    ),
+    (
+        BMI2,
+        "Bit Manipulation Instructions 2",
+        "flagless shifts, wide multiplication and bit deposit/extract",
+        "Intel Haswell, AMD Excavator",
+    ),
+    (
+        MOVBE,
+        "Move Big-Endian",
+        "loads and stores with byte swapping",
+        "Intel Atom, Intel Haswell, AMD Excavator",
+    ),
+    (
+        ADX,
+        "Multi-Precision Add-Carry Extensions",
+        "parallel carry chains for big integer arithmetic",
+        "Intel Broadwell, AMD Zen",
+    ),
+    (
+        RDRAND,
+        "Read Random Number",
+        "hardware random number generator output",
+        "Intel Ivy Bridge, AMD Excavator",
+    ),
+    (
+        RDSEED,
+        "Read Random Seed",
+        "hardware entropy source for seeding generators",
+        "Intel Broadwell, AMD Zen",
+    ),
+    (
+        SHA,
+        "SHA Extensions",
+        "hardware accelerated SHA-1 and SHA-256 rounds",
+        "Intel Goldmont, AMD Zen, Intel Ice Lake",
+    ),
+    (
+        FSGSBASE,
+        "FS/GS Base Access",
+        "reading and writing FS and GS segment bases from userspace",
+        "Intel Ivy Bridge, AMD Zen",
+    ),
+    (
+        XSAVE,
+        "Extended State Save",
+        "saving and restoring extended processor state",
+        "Intel Penryn, AMD Bulldozer",
+    ),
+    (
+        CLFLUSHOPT,
+        "Optimized Cache Line Flush",
+        "weakly ordered cache line flushing for persistent memory",
+        "Intel Skylake, AMD Zen",
+    ),
+    (
+        CLWB,
+        "Cache Line Write Back",
+        "writing back a cache line without evicting it",
+        "Intel Skylake-SP, AMD Zen 2",
+    ),
+    (
+        HLE,
+        "Hardware Lock Elision",
+        "transactional lock prefixes of Intel TSX",
+        "Intel Haswell (disabled on most parts)",
+    ),
+    (
+        RTM,
+        "Restricted Transactional Memory",
+        "explicit hardware transactions of Intel TSX",
+        "Intel Haswell (disabled on most parts)",
+    ),
+    (
+        AVX512F,
+        "AVX-512 Foundation",
+        "512-bit SIMD with mask registers and EVEX encoding",
+        "Intel Knights Landing, Intel Skylake-SP, AMD Zen 4",
+    ),
+    (
+        AVX512CD,
+        "AVX-512 Conflict Detection",
+        "detecting duplicate indices for vectorized scatters",
+        "Intel Knights Landing, Intel Skylake-SP, AMD Zen 4",
+    ),
+    (
+        AVX512BW,
+        "AVX-512 Byte and Word",
+        "512-bit operations on 8 and 16-bit integers",
+        "Intel Skylake-SP, AMD Zen 4",
+    ),
+    (
+        AVX512DQ,
+        "AVX-512 Doubleword and Quadword",
+        "512-bit operations on 32 and 64-bit elements",
+        "Intel Skylake-SP, AMD Zen 4",
+    ),
+    (
+        AVX512VL,
+        "AVX-512 Vector Length",
+        "AVX-512 instructions on 128 and 256-bit registers",
+        "Intel Skylake-SP, AMD Zen 4",
+    ),
+    (
+        AVX512_IFMA,
+        "AVX-512 Integer Fused Multiply-Add",
+        "52-bit integer multiply-add for big integer arithmetic",
+        "Intel Cannon Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_VBMI,
+        "AVX-512 Vector Byte Manipulation",
+        "byte permutations across the full register",
+        "Intel Cannon Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_VBMI2,
+        "AVX-512 Vector Byte Manipulation 2",
+        "byte and word compress, expand and concatenated shifts",
+        "Intel Ice Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_VNNI,
+        "AVX-512 Vector Neural Network Instructions",
+        "8 and 16-bit integer dot products for inference",
+        "Intel Cascade Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_BITALG,
+        "AVX-512 Bit Algorithms",
+        "per-element bit counting and shuffles",
+        "Intel Ice Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_VPOPCNTDQ,
+        "AVX-512 Vector Population Count",
+        "counting set bits of 32 and 64-bit elements",
+        "Intel Ice Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_BF16,
+        "AVX-512 BFloat16",
+        "bfloat16 conversion and dot products for machine learning",
+        "Intel Cooper Lake, AMD Zen 4",
+    ),
+    (
+        AVX512_FP16,
+        "AVX-512 Half Precision",
+        "arithmetic on 16-bit floating point elements",
+        "Intel Sapphire Rapids",
+    ),
+    (
+        AVX_VNNI,
+        "AVX Vector Neural Network Instructions",
+        "VEX encoded integer dot products without AVX-512",
+        "Intel Alder Lake, AMD Zen 5",
+    ),
+    (
+        GFNI,
+        "Galois Field New Instructions",
+        "GF(2^8) arithmetic and bit matrix transforms for cryptography and bit shuffling",
+        "Intel Goldmont Plus, Intel Ice Lake, AMD Zen 4",
+    ),
+    (
+        VAES,
+        "Vector AES",
+        "AES rounds on 256 and 512-bit registers",
+        "Intel Ice Lake, AMD Zen 3",
+    ),
+    (
+        VPCLMULQDQ,
+        "Vector Carry-Less Multiplication",
+        "carry-less multiplication on 256 and 512-bit registers",
+        "Intel Ice Lake, AMD Zen 3",
+    ),
+    (
+        AMX_TILE,
+        "Advanced Matrix Extensions",
+        "tile registers for matrix multiplication",
+        "Intel Sapphire Rapids",
+    ),
+    (
+        CET_IBT,
+        "CET Indirect Branch Tracking",
+        "endbr landing pads for indirect branch targets",
+        "Intel Tiger Lake, AMD Zen 3 (shadow stack only)",
+    ),
+    (
+        CET_SS,
+        "CET Shadow Stack",
+        "hardware protected return address stack",
+        "Intel Tiger Lake, AMD Zen 3",
+    ),
+    (
+        RDTSCP,
+        "Read Time-Stamp Counter and Processor ID",
+        "serializing time-stamp counter read",
+        "AMD K8, Intel Nehalem",
+    ),
+    (
+        RDPID,
+        "Read Processor ID",
+        "reading the current processor number",
+        "Intel Goldmont Plus, Intel Ice Lake, AMD Zen 2",
+    ),
+    (
+        MOVDIRI,
+        "Direct Store",
+        "doubleword and quadword stores bypassing the cache",
+        "Intel Tremont, Intel Tiger Lake",
+    ),
+    (
+        SERIALIZE,
+        "Serialize",
+        "instruction fetch and execution serialization",
+        "Intel Alder Lake",
+    ),
+    (
+        PREFETCHW,
+        "Prefetch for Write",
+        "prefetching a cache line in anticipation of a write",
+        "AMD K6-2, Intel Broadwell",
+    ),
+    (
+        D3NOW,
+        "3DNow!",
+        "AMD-only legacy 64-bit floating point SIMD",
+        "AMD K6-2 (dropped in Bulldozer)",
+    ),
+    (
+        VMX,
+        "Virtual Machine Extensions",
+        "Intel hardware virtualization (VT-x), privileged",
+        "Intel Prescott",
+    ),
+    (
+        SVM,
+        "Secure Virtual Machine",
+        "AMD hardware virtualization (AMD-V), privileged",
+        "AMD K8 (Pacifica)",
+    ),
+];
+
+/// Prints a description of the feature and the CPUID leaf reporting it.
+pub fn print(feature: CpuidFeature, output_mode: OutputMode) {
+    let description = DESCRIPTIONS.iter().find(|(f, ..)| *f == feature);
+    let labels = output_mode > OutputMode::Quiet;
+
+    match (description, labels) {
+        (Some((_, name, purpose, introduced)), true) => {
+            println!("{feature:?}: {name}");
+            println!("    Purpose: {purpose}");
+            println!("    Introduced: {introduced}");
+        }
+        (Some((_, name, purpose, introduced)), _) => {
+            println!("{name}");
+            println!("{purpose}");
+            println!("{introduced}");
+        }
+        (_, true) => println!("{feature:?}: no description available"),
+        _ => {}
+    }
+
+    match labels {
+        true => println!("    CPUID: {}", leaves::leaf(feature)),
+        _ => println!("{}", leaves::leaf(feature)),
+    }
+}
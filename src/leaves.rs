@@ -0,0 +1,131 @@
+use iced_x86::CpuidFeature::{self, *};
+
+/// CPUID leaves and registers reporting each feature, as in Intel and AMD manuals.
+const LEAVES: &[(&str, &[CpuidFeature])] = &[
+    (
+        "Leaf 1 EDX",
+        &[FPU, TSC, MSR, CX8, SEP, CMOV, CLFSH, MMX, FXSR, SSE, SSE2],
+    ),
+    (
+        "Leaf 1 ECX",
+        &[
+            SSE3, PCLMULQDQ, MONITOR, VMX, SMX, SSSE3, FMA, CMPXCHG16B, SSE4_1, SSE4_2, MOVBE,
+            POPCNT, AES, XSAVE, AVX, F16C, RDRAND,
+        ],
+    ),
+    (
+        "Leaf 7 EBX",
+        &[
+            FSGSBASE,
+            SGX1,
+            BMI1,
+            HLE,
+            AVX2,
+            BMI2,
+            INVPCID,
+            RTM,
+            MPX,
+            AVX512F,
+            AVX512DQ,
+            RDSEED,
+            ADX,
+            SMAP,
+            AVX512_IFMA,
+            CLFLUSHOPT,
+            CLWB,
+            AVX512PF,
+            AVX512ER,
+            AVX512CD,
+            SHA,
+            AVX512BW,
+            AVX512VL,
+        ],
+    ),
+    (
+        "Leaf 7 ECX",
+        &[
+            PREFETCHWT1,
+            AVX512_VBMI,
+            PKU,
+            WAITPKG,
+            AVX512_VBMI2,
+            CET_SS,
+            GFNI,
+            VAES,
+            VPCLMULQDQ,
+            AVX512_VNNI,
+            AVX512_BITALG,
+            AVX512_VPOPCNTDQ,
+            KL,
+            RDPID,
+            CLDEMOTE,
+            MOVDIRI,
+            MOVDIR64B,
+            ENQCMD,
+        ],
+    ),
+    (
+        "Leaf 7 EDX",
+        &[
+            AVX512_4VNNIW,
+            AVX512_4FMAPS,
+            UINTR,
+            AVX512_VP2INTERSECT,
+            SERIALIZE,
+            TSXLDTRK,
+            PCONFIG,
+            CET_IBT,
+            AMX_BF16,
+            AVX512_FP16,
+            AMX_TILE,
+            AMX_INT8,
+        ],
+    ),
+    (
+        "Leaf 7.1 EAX",
+        &[
+            RAO_INT,
+            AVX_VNNI,
+            AVX512_BF16,
+            CMPCCXADD,
+            AMX_FP16,
+            FRED,
+            LKGS,
+            HRESET,
+            AVX_IFMA,
+        ],
+    ),
+    (
+        "Leaf 7.1 EDX",
+        &[AVX_VNNI_INT8, AVX_NE_CONVERT, PREFETCHITI],
+    ),
+    ("Leaf 0xD.1 EAX", &[XSAVEOPT, XSAVEC, XSAVES]),
+    (
+        "Leaf 0x80000001 ECX",
+        &[
+            SVM, LZCNT, SSE4A, PREFETCHW, SKINIT, XOP, LWP, FMA4, TBM, MONITORX,
+        ],
+    ),
+    (
+        "Leaf 0x80000001 EDX",
+        &[SYSCALL, X64, RDTSCP, D3NOWEXT, D3NOW],
+    ),
+    (
+        "Leaf 0x80000008 EBX",
+        &[CLZERO, MCOMMIT, WBNOINVD, INVLPGB, RDPRU],
+    ),
+];
+
+/// Features without a dedicated CPUID bit.
+const OTHER: &str = "Other";
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    LEAVES.iter().map(|(name, _)| *name).chain([OTHER])
+}
+
+pub fn leaf(feature: CpuidFeature) -> &'static str {
+    LEAVES
+        .iter()
+        .find(|(_, features)| features.contains(&feature))
+        .map_or(OTHER, |(name, _)| name)
+}
@@ -1,13 +1,33 @@
-use std::{env, fs::File, process::ExitCode};
+use std::{
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufReader, Cursor, IsTerminal, Read, Seek, SeekFrom},
+    process::{Command, ExitCode, Stdio},
+};
 
 mod binary;
 mod cli;
 mod decoder;
 mod error;
+mod explain;
+mod leaves;
+#[cfg(target_os = "linux")]
+mod process;
+mod profiles;
+mod progress;
+mod tasks;
+mod warnings;
+
+use flate2::bufread::MultiGzDecoder;
+use iced_x86::DecoderOptions;
 
 use crate::{
-    cli::{Config, OutputMode},
-    error::{AppError, R},
+    binary::{Binary, Options, Segment, Source, Symbol},
+    cli::{Config, Format, Mode, OutputMode, Style},
+    decoder::{DetailOptions, Params, Summary, BASELINE},
+    error::{AppError, ArgError, R},
+    progress::{Bar, Progress},
+    tasks::Task,
 };
 
 #[macro_export]
@@ -17,23 +37,530 @@ macro_rules! check {
     };
 }
 
-fn run_for(path: &str, details: bool, output_mode: OutputMode) -> R<()> {
+/// Invalid instructions percentage hinting at data or packed code.
+const INVALID_THRESHOLD: f64 = 10.0;
+
+struct Target<'a> {
+    file: Box<dyn Source>,
+    binary: Binary,
+    params: Params<'a>,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn is_gzip(file: &mut File) -> R<bool> {
+    let mut magic = [0; GZIP_MAGIC.len()];
+    let result = file.read_exact(&mut magic);
+    file.rewind()?;
+    Ok(result.is_ok() && magic == GZIP_MAGIC)
+}
+
+/// Unpacks the whole file into memory, as `object` needs random access.
+fn decompress(file: File, output_mode: OutputMode) -> R<Cursor<Vec<u8>>> {
+    let mut data = Vec::new();
+    MultiGzDecoder::new(BufReader::new(file))
+        .read_to_end(&mut data)
+        .map_err(AppError::Gzip)?;
+
     if output_mode > OutputMode::Normal {
-        println!("Reading '{path}'...");
+        println!("Decompressed: {} bytes", data.len());
+    }
+
+    Ok(Cursor::new(data))
+}
+
+/// Opens the file and maps its code sections.
+fn load(path: &str, config: &Config) -> R<(Box<dyn Source>, Binary)> {
+    let output_mode = config.output_mode;
+
+    if let Some(bytes) = &config.hex {
+        let mut file = Cursor::new(bytes.clone());
+        let binary = binary::raw(&mut file, config.base)?;
+        return Ok((Box::new(file), binary));
+    }
+
+    let metadata = match fs::symlink_metadata(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => E!(AppError::NotFound(path.into())),
+        m => m?,
+    };
+
+    if metadata.is_symlink() {
+        check!(!config.no_follow, AppError::Symlink(path.into()));
+        let target = match fs::canonicalize(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => E!(AppError::BrokenLink(path.into())),
+            t => t?,
+        };
+        if output_mode > OutputMode::Normal {
+            println!("Following link to '{}'", target.display());
+        }
     }
 
     let mut file = File::open(path)?;
-    check!(
-        !file.metadata()?.file_type().is_dir(),
-        AppError::WrongTarget
-    );
+    let file_type = file.metadata()?.file_type();
+    check!(!file_type.is_dir(), AppError::IsDirectory(path.into()));
+    check!(file_type.is_file(), AppError::WrongTarget);
+
+    let mut file: Box<dyn Source> = match config.gzip || is_gzip(&mut file)? {
+        true => Box::new(decompress(file, output_mode)?),
+        _ => Box::new(file),
+    };
+
+    let symbols = config.symbols
+        || config.used_by.is_some()
+        || config.symbol.is_some()
+        || config.reachable_only;
+    let binary = match config.raw {
+        true => binary::raw(&mut file, config.base)?,
+        _ => binary::parse(
+            &mut file,
+            Options {
+                format: config.format_hint,
+                symbols,
+                arch: config.arch,
+                exec_only: config.exec_only,
+                kinds: &config.section_kinds,
+                section_bitness: &config.section_bitness,
+            },
+            output_mode,
+        )
+        .map_err(|e| match e.downcast::<object::Error>() {
+            // The cause is rarely useful to anyone but developers.
+            Ok(e) => AppError::NotBinary(
+                path.into(),
+                (output_mode > OutputMode::Normal).then_some(*e),
+            )
+            .into(),
+            Err(e) => e,
+        })?,
+    };
+
+    Ok((file, binary))
+}
+
+fn open<'a>(path: &str, config: &'a Config, bar: &'a Bar) -> R<Target<'a>> {
+    let output_mode = config.output_mode;
+
+    if output_mode > OutputMode::Normal {
+        println!("Reading '{path}'...");
+    }
+
+    #[cfg(target_os = "linux")]
+    let loaded = match config.pid {
+        Some(pid) => process::attach(pid, output_mode)?,
+        _ => load(path, config)?,
+    };
+    #[cfg(not(target_os = "linux"))]
+    let loaded = load(path, config)?;
+
+    let (mut file, mut binary) = loaded;
+    check!(!binary.sections.is_empty(), AppError::NoText);
+
+    // Gaps between functions are likely data, like jump tables and constant pools.
+    if config.reachable_only && config.symbol.is_none() {
+        let functions: Vec<_> = binary
+            .symbols
+            .iter()
+            .map(Symbol::segment)
+            .filter(|f| {
+                binary
+                    .sections
+                    .iter()
+                    .any(|s| (s.offset..s.offset + s.size).contains(&f.offset))
+            })
+            .collect();
+
+        match functions.is_empty() {
+            true => warnings::warn(
+                "no function symbols found, decoding whole sections".into(),
+                false,
+            ),
+            _ => {
+                binary.sections = binary::merge_segments(functions);
+                if output_mode > OutputMode::Normal {
+                    println!("Function ranges: {}", binary.sections.len());
+                }
+            }
+        }
+    }
+
+    if output_mode > OutputMode::Normal {
+        let code: u64 = binary.sections.iter().map(|s| s.size).sum();
+        // Process memory has no end to seek to.
+        match file.seek(SeekFrom::End(0)) {
+            Ok(size) => println!(
+                "Code: {code} bytes, {:.0}% of file",
+                tasks::ratio(code, size)
+            ),
+            _ => println!("Code: {code} bytes"),
+        }
+    }
+
+    if let Some(name) = &config.symbol {
+        check!(!binary.symbols.is_empty(), AppError::NoSymbols);
+        let matched: Vec<_> = binary
+            .symbols
+            .iter()
+            .filter(|s| cli::glob(name.as_bytes(), s.name.as_bytes()))
+            .collect();
+        check!(!matched.is_empty(), AppError::SymbolNotFound(name.clone()));
+        if output_mode > OutputMode::Normal {
+            for symbol in &matched {
+                let Symbol { address, size, .. } = symbol;
+                println!("Symbol: {} => 0x{address:x}, {size} bytes", symbol.name);
+            }
+        }
+        // Aliases share code, merging avoids decoding it twice.
+        binary.sections = binary::merge_segments(matched.iter().map(|s| s.segment()).collect());
+    }
+
+    // Decoding at an offset not aligned to instruction boundaries reveals an alternate
+    // instruction stream, like code hidden by overlapping instructions.
+    if let Some(from) = config.decode_from {
+        binary.sections = binary
+            .sections
+            .iter()
+            .filter_map(|s| s.starting_at(from))
+            .collect();
+        check!(!binary.sections.is_empty(), AppError::NoCodeAt(from));
+        if output_mode > OutputMode::Normal {
+            let address = binary.sections[0].address;
+            println!("Decoding from offset 0x{from:x}, address 0x{address:x}");
+        }
+    }
+
+    // Raw code has no header to check against.
+    if let Some(bitness) = config
+        .bitness
+        .filter(|&b| !config.raw && b != binary.bitness)
+    {
+        check!(
+            !config.strict,
+            AppError::WrongBitness(bitness, binary.bitness)
+        );
+        warnings::warn(
+            format!(
+                "decoding as {bitness}-bit, but the file is {}-bit",
+                binary.bitness
+            ),
+            false,
+        );
+    }
+
+    let bitness = config.bitness.unwrap_or(binary.bitness);
+    if output_mode > OutputMode::Normal {
+        println!("Bitness: {bitness}");
+    }
+
+    let mut options = match config.check_invalid {
+        true => DecoderOptions::NONE,
+        _ => DecoderOptions::NO_INVALID_CHECK,
+    };
+    if config.amd {
+        options |= DecoderOptions::AMD;
+    }
+    let params = Params {
+        bitness,
+        options,
+        count_by: config.count_by,
+        trim_padding: config.trim_padding,
+        jobs: config.jobs,
+        ignore_padding: config.ignore_padding,
+        max_instructions: config.max_instructions,
+        excluded: &config.excluded,
+        baseline: config.baseline.as_deref().unwrap_or(BASELINE),
+        progress: (config.progress && io::stderr().is_terminal()).then_some(bar as &dyn Progress),
+        verify_parallel: config.verify_parallel,
+    };
+
+    Ok(Target {
+        file,
+        binary,
+        params,
+    })
+}
+
+/// Adds the file path to bare IO errors.
+fn context<T>(path: &str, result: R<T>) -> R<T> {
+    result.map_err(|e| match e.downcast::<io::Error>() {
+        Ok(e) => AppError::Io(path.into(), *e).into(),
+        Err(e) => e,
+    })
+}
+
+fn count(path: &str, config: &Config) -> R<Vec<u64>> {
+    context(path, {
+        let bar = Bar::new();
+        let mut target = open(path, config, &bar)?;
+        decoder::count(
+            &mut target.file,
+            &target.binary.sections,
+            target.params,
+            config.output_mode,
+        )
+    })
+}
 
-    let (sections, bitness) = binary::parse(&file, output_mode)?;
-    check!(!sections.is_empty(), AppError::NoText);
-    decoder::run(&mut file, &sections, bitness, details, output_mode)?;
+fn run_diff(path: &str, other: &str, config: &Config) -> R<()> {
+    let old = count(other, config)?;
+    let new = count(path, config)?;
+
+    let gained = decoder::print_diff(&old, &new, config.output_mode);
+    check!(!(config.exit_code && gained), AppError::CheckFailed);
     Ok(())
 }
 
+/// Prints segments to be decoded, one per line.
+fn list_segments(sections: &[Segment], output_mode: OutputMode) {
+    let indent = match output_mode > OutputMode::Quiet {
+        true => {
+            println!("Segments: {}", sections.len());
+            "    "
+        }
+        _ => "",
+    };
+    for s in sections {
+        println!(
+            "{indent}0x{:x} - 0x{:x}, offset 0x{:x}, {} bytes",
+            s.address,
+            s.address + s.size,
+            s.offset,
+            s.size
+        );
+    }
+}
+
+fn sampled_note(params: Params, sampled: bool, output_mode: OutputMode) {
+    if let Some(max) = params.max_instructions.filter(|_| sampled) {
+        if output_mode > OutputMode::Bare {
+            println!("Note: sampled first {max} instructions.");
+        }
+    }
+}
+
+fn run_for(path: &str, config: &Config) -> R<()> {
+    let output_mode = config.output_mode;
+
+    // Bars are for humans only.
+    let style = Style {
+        bars: config.style.bars && output_mode > OutputMode::Quiet && io::stdout().is_terminal(),
+        ..config.style
+    };
+
+    if let (Some(config_path), true) = (&config.config_path, output_mode > OutputMode::Normal) {
+        println!("Config: '{config_path}'");
+    }
+
+    if let Some(other) = &config.diff {
+        return run_diff(path, other, config);
+    }
+
+    let bar = Bar::new();
+    let Target {
+        mut file,
+        binary,
+        params,
+    } = open(path, config, &bar)?;
+
+    if config.list_segments {
+        list_segments(&binary.sections, output_mode);
+        return Ok(());
+    }
+
+    if let Some(feature) = config.used_by {
+        check!(!binary.symbols.is_empty(), AppError::NoSymbols);
+        return decoder::run_used_by(
+            &mut file,
+            &binary.sections,
+            &binary.symbols,
+            params,
+            feature,
+            output_mode,
+        );
+    }
+
+    if config.symbols {
+        if !binary.symbols.is_empty() {
+            return decoder::run_symbols(
+                &mut file,
+                &binary.sections,
+                &binary.symbols,
+                params,
+                output_mode,
+            );
+        }
+
+        if output_mode > OutputMode::Bare {
+            println!("Note: no function symbols found, falling back to whole-section analysis.");
+        }
+    }
+
+    macro_rules! task {
+        ($t: expr) => {
+            run_task(&mut file, &binary.sections, params, $t, config)?
+        };
+    }
+
+    let summary = match config.mode {
+        Mode::Detect => decoder::run(
+            &mut file,
+            &binary.sections,
+            params,
+            None,
+            style,
+            output_mode,
+        )?,
+        Mode::Details => decoder::run(
+            &mut file,
+            &binary.sections,
+            params,
+            Some(&DetailOptions {
+                samples: config.samples,
+                mnemonics: config.mnemonics.clone(),
+            }),
+            style,
+            output_mode,
+        )?,
+        Mode::Top => decoder::run_top(
+            &mut file,
+            &binary.sections,
+            params,
+            &DetailOptions {
+                samples: 0,
+                mnemonics: config.mnemonics.clone(),
+            },
+            style,
+            output_mode,
+        )?,
+        Mode::Level => task!(tasks::Level::default()),
+        Mode::Legacy => task!(tasks::Legacy::default()),
+        Mode::Privileged => task!(tasks::Privileged::default()),
+        Mode::Cet => task!(tasks::Cet::default()),
+        Mode::Rng => task!(tasks::Rng::default()),
+        Mode::Tsx => task!(tasks::Tsx::default()),
+        Mode::SseAvx => task!(tasks::SseAvx::default()),
+        Mode::Branches => task!(tasks::Branches::default()),
+        Mode::Stack => task!(tasks::Stack::default()),
+        Mode::Lengths => task!(tasks::Lengths::default()),
+        Mode::Prefixes => task!(tasks::Prefixes::default()),
+        Mode::X64 => task!(tasks::X64::default()),
+        Mode::Mnemonics => task!(tasks::Mnemonics::default()),
+        Mode::Categories => task!(tasks::Categories::default()),
+        Mode::Overlap => task!(tasks::Overlap::default()),
+        Mode::Count => {
+            // Features are only needed for checks and the share beyond the baseline,
+            // decoding alone is much faster.
+            let features = config.summary
+                || !config.forbid.is_empty()
+                || config.profile.is_some()
+                || config.max_level.is_some();
+            decoder::run_count(
+                &mut file,
+                &binary.sections,
+                params,
+                features,
+                (output_mode > OutputMode::Quiet).then_some(params.baseline),
+                style.precision,
+                output_mode,
+            )?
+        }
+        Mode::Entropy => task!(tasks::Entropy::default()),
+        Mode::Disasm | Mode::InsnFeatures => {
+            let decoded = decoder::run_disasm(
+                &mut file,
+                &binary.sections,
+                params,
+                config.filter_feature,
+                config.mode == Mode::InsnFeatures,
+                output_mode,
+            )?;
+            sampled_note(params, decoded.sampled, output_mode);
+            return Ok(());
+        }
+    };
+
+    // Trimmed padding or tiny symbols may leave nothing to decode.
+    if summary.total == 0 && output_mode > OutputMode::Bare {
+        println!("Note: no instructions decoded.");
+    }
+
+    sampled_note(params, summary.sampled, output_mode);
+
+    let padding = summary.padding;
+    if padding > 0 && output_mode > OutputMode::Bare {
+        println!("Note: {padding} padding instructions (int3, nop) not counted.");
+    }
+
+    let invalid = tasks::ratio(summary.invalid, summary.total);
+    if invalid > INVALID_THRESHOLD {
+        warnings::warn(
+            format!("high invalid instructions ratio ({invalid:.0}%)"),
+            false,
+        );
+    }
+
+    if config.style.format == Format::Ndjson {
+        decoder::print_ndjson(path, &summary, &warnings::all(), config.style);
+    }
+
+    if config.summary {
+        decoder::print_summary(&summary);
+    }
+
+    let found = summary.found;
+    let forbidden: Vec<_> = found
+        .iter()
+        .copied()
+        .filter(|f| config.forbid.contains(f))
+        .collect();
+    check!(forbidden.is_empty(), AppError::Forbidden(forbidden));
+
+    if let Some(profile) = config.profile {
+        let missing: Vec<_> = found
+            .iter()
+            .copied()
+            .filter(|&f| !profile.supports(f))
+            .collect();
+        check!(
+            missing.is_empty(),
+            AppError::Incompatible(profile.name, missing)
+        );
+        if output_mode > OutputMode::Quiet {
+            println!("Compatible with {}", profile.name);
+        }
+    }
+
+    // Just the exit code in quiet mode, for deployment gating.
+    if let Some(level) = config.max_level {
+        let above: Vec<_> = found.into_iter().filter(|&f| !level.supports(f)).collect();
+        match (above.is_empty(), output_mode > OutputMode::Quiet) {
+            (true, true) => println!("Within {}", level.name),
+            (true, _) => {}
+            (_, true) => E!(AppError::Incompatible(level.name, above)),
+            _ => E!(AppError::CheckFailed),
+        }
+    }
+    Ok(())
+}
+
+fn run_task(
+    file: &mut impl Source,
+    sections: &[Segment],
+    params: Params,
+    mut task: impl Task + Default + Send + PartialEq + fmt::Debug,
+    config: &Config,
+) -> R<Summary> {
+    let summary = decoder::run_task(
+        file,
+        sections,
+        params,
+        &mut task,
+        config.style.precision,
+        config.output_mode,
+    )?;
+    check!(!(config.exit_code && task.found()), AppError::CheckFailed);
+    Ok(summary)
+}
+
 fn print_help() {
     let bin = env::current_exe().ok();
     println!(
@@ -44,15 +571,62 @@ fn print_help() {
     );
 }
 
+/// Runs the tool again with its output piped through `$PAGER`, `less` by default.
+/// Returns `None` if the pager is disabled or fails to start.
+fn run_paged() -> R<Option<i32>> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".into());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        return Ok(None);
+    };
+
+    let Ok(mut pager) = Command::new(program)
+        .args(words)
+        // Quits right away if everything fits the screen, keeping the output visible.
+        .env("LESS", env::var("LESS").unwrap_or_else(|_| "FRX".into()))
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        return Ok(None);
+    };
+
+    // The output of the child is not a terminal, so it does not page again.
+    let status = Command::new(env::current_exe()?)
+        .args(env::args_os().skip(1))
+        .stdout(pager.stdin.take().map_or(Stdio::inherit(), Stdio::from))
+        .status()?;
+    pager.wait()?;
+    Ok(Some(status.code().unwrap_or(1)))
+}
+
 fn run_app() -> R<()> {
     let config = cli::read_args(env::args().skip(1))?;
 
+    // Machine readable output is never paged.
+    if let Some(config) = &config {
+        if config.pager && io::stdout().is_terminal() && !config.style.format.is_machine() {
+            if let Some(code) = run_paged()? {
+                std::process::exit(code);
+            }
+        }
+    }
+
     match config {
         Some(Config {
-            file_path: Some(path),
-            details,
+            explain: Some(feature),
             output_mode,
-        }) => run_for(&path, details, output_mode)?,
+            ..
+        }) => explain::print(feature, output_mode),
+        Some(
+            ref config @ Config {
+                file_path: Some(ref path),
+                ..
+            },
+        ) => {
+            let result = context(path, run_for(path, config));
+            warnings::print(config.output_mode, config.style.format == Format::Markdown);
+            result?
+        }
         _ => print_help(),
     }
 
@@ -62,6 +636,10 @@ fn run_app() -> R<()> {
 fn main() -> ExitCode {
     run_app().err().map_or(ExitCode::SUCCESS, |e| {
         eprintln!("Error: {e}");
-        ExitCode::FAILURE
+        match (e.downcast_ref::<AppError>(), e.is::<ArgError>()) {
+            (Some(e), _) => ExitCode::from(e.code()),
+            (_, true) => ExitCode::from(7),
+            _ => ExitCode::FAILURE,
+        }
     })
 }
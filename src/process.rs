@@ -0,0 +1,83 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+};
+
+use crate::{
+    binary::{Binary, Segment, Source},
+    cli::OutputMode,
+    error::{AppError, R},
+};
+
+fn access_error(pid: u32, e: io::Error) -> Box<dyn std::error::Error> {
+    match e.kind() {
+        io::ErrorKind::NotFound => AppError::NoProcess(pid).into(),
+        io::ErrorKind::PermissionDenied => AppError::NoAccess(pid).into(),
+        _ => e.into(),
+    }
+}
+
+/// Bitness from the ELF class of the process executable, 64 if unknown.
+fn read_bitness(pid: u32) -> u32 {
+    let mut header = [0; 5];
+    let class = File::open(format!("/proc/{pid}/exe"))
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map(|_| header[4]);
+    match class {
+        Ok(1) => 32,
+        _ => 64,
+    }
+}
+
+/// Parses an executable mapping from a `/proc/N/maps` line.
+/// The memory file is indexed by virtual addresses, so they are used as offsets.
+fn map_line(line: &str, output_mode: OutputMode) -> Option<Segment> {
+    let mut fields = line.split_whitespace();
+    let (start, end) = fields.next()?.split_once('-')?;
+    let perms = fields.next()?;
+    let name = fields.nth(3).unwrap_or_default();
+
+    // vsyscall page is not readable through the memory file.
+    if !perms.contains('x') || name == "[vsyscall]" {
+        return None;
+    }
+
+    let start = u64::from_str_radix(start, 16).ok()?;
+    let end = u64::from_str_radix(end, 16).ok()?;
+    let size = end.checked_sub(start)?;
+
+    if output_mode > OutputMode::Normal {
+        println!("    {name} => 0x{start:x} - 0x{end:x}, {size} bytes, {perms}");
+    }
+
+    Some(Segment {
+        offset: start,
+        size,
+        address: start,
+        bitness: None,
+    })
+}
+
+/// Maps executable regions of a running process.
+pub fn attach(pid: u32, output_mode: OutputMode) -> R<(Box<dyn Source>, Binary)> {
+    let maps = fs::read_to_string(format!("/proc/{pid}/maps")).map_err(|e| access_error(pid, e))?;
+    let file = File::open(format!("/proc/{pid}/mem")).map_err(|e| access_error(pid, e))?;
+
+    if output_mode > OutputMode::Normal {
+        println!("Executable mappings: ");
+    }
+
+    let sections = maps
+        .lines()
+        .filter_map(|line| map_line(line, output_mode))
+        .collect();
+
+    Ok((
+        Box::new(file),
+        Binary {
+            sections,
+            symbols: Vec::new(),
+            bitness: read_bitness(pid),
+        },
+    ))
+}
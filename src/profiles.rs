@@ -0,0 +1,230 @@
+use iced_x86::CpuidFeature::{self, *};
+
+use crate::decoder::BASELINE;
+
+pub struct Profile {
+    pub name: &'static str,
+    /// Profile this one extends.
+    parent: Option<&'static str>,
+    features: &'static [CpuidFeature],
+}
+
+/// Common microarchitectures, each listing features added to its parent.
+const PROFILES: &[Profile] = &[
+    Profile {
+        name: "x86-64",
+        parent: None,
+        // `endbr` is a NOP on CPUs without CET.
+        features: &[CPUID, CLFSH, MSR, RDPMC, SEP, CET_IBT],
+    },
+    Profile {
+        name: "x86-64-v2",
+        parent: Some("x86-64"),
+        features: &[CMPXCHG16B, POPCNT, SSE3, SSE4_1, SSE4_2, SSSE3],
+    },
+    Profile {
+        name: "x86-64-v3",
+        parent: Some("x86-64-v2"),
+        features: &[AVX, AVX2, BMI1, BMI2, F16C, FMA, LZCNT, MOVBE, XSAVE],
+    },
+    Profile {
+        name: "x86-64-v4",
+        parent: Some("x86-64-v3"),
+        features: &[AVX512F, AVX512BW, AVX512CD, AVX512DQ, AVX512VL],
+    },
+    Profile {
+        name: "core2",
+        parent: Some("x86-64"),
+        features: &[SSE3, SSSE3, CMPXCHG16B, MONITOR],
+    },
+    Profile {
+        name: "nehalem",
+        parent: Some("core2"),
+        features: &[SSE4_1, SSE4_2, POPCNT, RDTSCP],
+    },
+    Profile {
+        name: "westmere",
+        parent: Some("nehalem"),
+        features: &[AES, PCLMULQDQ],
+    },
+    Profile {
+        name: "sandybridge",
+        parent: Some("westmere"),
+        features: &[AVX, XSAVE, XSAVEOPT],
+    },
+    Profile {
+        name: "ivybridge",
+        parent: Some("sandybridge"),
+        features: &[F16C, RDRAND, FSGSBASE],
+    },
+    Profile {
+        name: "haswell",
+        parent: Some("ivybridge"),
+        features: &[AVX2, BMI1, BMI2, FMA, LZCNT, MOVBE],
+    },
+    Profile {
+        name: "skylake",
+        parent: Some("haswell"),
+        features: &[ADX, RDSEED, PREFETCHW, CLFLUSHOPT, XSAVEC, XSAVES],
+    },
+    Profile {
+        name: "skylake-avx512",
+        parent: Some("skylake"),
+        features: &[AVX512F, AVX512CD, AVX512BW, AVX512DQ, AVX512VL, CLWB, PKU],
+    },
+    Profile {
+        name: "icelake",
+        parent: Some("skylake-avx512"),
+        features: &[
+            AVX512_VBMI,
+            AVX512_IFMA,
+            AVX512_VBMI2,
+            AVX512_VNNI,
+            AVX512_BITALG,
+            AVX512_VPOPCNTDQ,
+            GFNI,
+            VAES,
+            VPCLMULQDQ,
+            SHA,
+            RDPID,
+        ],
+    },
+    Profile {
+        name: "zen",
+        parent: Some("haswell"),
+        features: &[
+            ADX, RDSEED, PREFETCHW, CLFLUSHOPT, XSAVEC, XSAVES, SHA, SSE4A, CLZERO, MONITORX,
+        ],
+    },
+    Profile {
+        name: "zen2",
+        parent: Some("zen"),
+        features: &[CLWB, RDPID, WBNOINVD, RDPRU],
+    },
+    Profile {
+        name: "zen3",
+        parent: Some("zen2"),
+        features: &[VAES, VPCLMULQDQ, PKU],
+    },
+    Profile {
+        name: "zen4",
+        parent: Some("zen3"),
+        features: &[
+            AVX512F,
+            AVX512CD,
+            AVX512BW,
+            AVX512DQ,
+            AVX512VL,
+            AVX512_IFMA,
+            AVX512_VBMI,
+            AVX512_VBMI2,
+            AVX512_VNNI,
+            AVX512_BITALG,
+            AVX512_VPOPCNTDQ,
+            AVX512_BF16,
+            GFNI,
+        ],
+    },
+    Profile {
+        name: "sapphirerapids",
+        parent: Some("icelake"),
+        features: &[
+            AMX_TILE,
+            AMX_INT8,
+            AMX_BF16,
+            AVX512_FP16,
+            AVX512_BF16,
+            AVX_VNNI,
+            SERIALIZE,
+            TSXLDTRK,
+            WAITPKG,
+            MOVDIRI,
+            MOVDIR64B,
+            ENQCMD,
+            CLDEMOTE,
+            PTWRITE,
+            UINTR,
+            CLWB,
+            WBNOINVD,
+            PCONFIG,
+        ],
+    },
+];
+
+/// x86-64 psABI microarchitecture levels, from the highest.
+const LEVELS: &[&str] = &["x86-64-v4", "x86-64-v3", "x86-64-v2"];
+
+/// psABI name of the lowest level, the `x86-64` profile.
+const FLOOR: &str = "x86-64-v1";
+
+/// Microarchitectures in order of release, to date features by the first one adding them.
+const ERAS: &[&str] = &[
+    "x86-64",
+    "core2",
+    "nehalem",
+    "westmere",
+    "sandybridge",
+    "ivybridge",
+    "haswell",
+    "skylake",
+    "zen",
+    "skylake-avx512",
+    "zen2",
+    "icelake",
+    "zen3",
+    "zen4",
+    "sapphirerapids",
+];
+
+/// Finds a profile by name, `x86-64` also by its psABI level name.
+pub fn find(name: &str) -> Option<&'static Profile> {
+    let name = match name.eq_ignore_ascii_case(FLOOR) {
+        true => "x86-64",
+        _ => name,
+    };
+    PROFILES.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Finds a psABI level profile by name.
+pub fn find_level(name: &str) -> Option<&'static Profile> {
+    find(name).filter(|p| p.name == "x86-64" || LEVELS.contains(&p.name))
+}
+
+/// The highest psABI level any of the features was added in.
+pub fn level(found: &[CpuidFeature]) -> &'static str {
+    LEVELS
+        .iter()
+        .filter_map(|&name| find(name))
+        .find(|p| p.features.iter().any(|f| found.contains(f)))
+        .map_or(FLOOR, |p| p.name)
+}
+
+/// Release order of the microarchitecture first adding the feature.
+/// Baseline features are the oldest, ones missing from all profiles are considered the newest.
+pub fn era(feature: CpuidFeature) -> usize {
+    if BASELINE.contains(&feature) {
+        return 0;
+    }
+    ERAS.iter()
+        .filter_map(|&name| find(name))
+        .position(|p| p.features.contains(&feature))
+        .map_or(ERAS.len() + 1, |i| i + 1)
+}
+
+impl Profile {
+    /// All features the profile supports, including inherited ones.
+    pub fn supported(&self) -> Vec<CpuidFeature> {
+        CpuidFeature::values()
+            .filter(|&f| self.supports(f))
+            .collect()
+    }
+
+    pub fn supports(&self, feature: CpuidFeature) -> bool {
+        BASELINE.contains(&feature)
+            || self.features.contains(&feature)
+            || self
+                .parent
+                .and_then(find)
+                .is_some_and(|p| p.supports(feature))
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Receives decoding progress, shared by all threads of a run.
+pub trait Progress: Sync {
+    /// Starts over with `total` bytes of all segments to decode.
+    fn start(&self, total: u64);
+    /// Reports `position` bytes of the current segment decoded.
+    fn update(&self, position: u64);
+    /// Marks the current segment of `size` bytes as decoded.
+    fn advance(&self, size: u64);
+    /// Ends reporting before the results are printed.
+    fn finish(&self);
+}
+
+/// Progress bar drawn on stderr.
+pub struct Bar {
+    /// Bytes of all segments to decode.
+    total: AtomicU64,
+    /// Bytes of segments decoded completely.
+    done: AtomicU64,
+    /// Last printed percentage, to redraw only on change.
+    shown: AtomicU64,
+}
+
+const WIDTH: usize = 40;
+
+impl Bar {
+    pub fn new() -> Self {
+        Bar {
+            total: AtomicU64::new(0),
+            done: AtomicU64::new(0),
+            shown: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl Progress for Bar {
+    fn start(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+        self.done.store(0, Ordering::Relaxed);
+        self.shown.store(u64::MAX, Ordering::Relaxed);
+        self.update(0);
+    }
+
+    fn update(&self, position: u64) {
+        let total = self.total.load(Ordering::Relaxed).max(1);
+        let done = (self.done.load(Ordering::Relaxed) + position).min(total);
+        let percent = done * 100 / total;
+        if self.shown.swap(percent, Ordering::Relaxed) == percent {
+            return;
+        }
+
+        let filled = percent as usize * WIDTH / 100;
+        eprint!(
+            "\r[{}{}] {percent:>3}%",
+            "#".repeat(filled),
+            ".".repeat(WIDTH - filled)
+        );
+        io::stderr().flush().ok();
+    }
+
+    fn advance(&self, size: u64) {
+        self.done.fetch_add(size, Ordering::Relaxed);
+        self.update(0);
+    }
+
+    fn finish(&self) {
+        eprint!("\r{:1$}\r", "", WIDTH + 7);
+    }
+}
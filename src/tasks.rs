@@ -0,0 +1,1126 @@
+use iced_x86::{
+    CodeSize, ConditionCode, CpuidFeature, EncodingKind, FlowControl, Instruction, Mnemonic,
+    OpKind, Register,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use crate::{cli::OutputMode, profiles};
+
+pub trait Task {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]);
+    fn print(&self, output_mode: OutputMode, precision: usize);
+
+    /// Combines results of the task run over other code.
+    fn merge(&mut self, other: Self)
+    where
+        Self: Sized;
+
+    /// Whether the task found an issue worth failing on with `--exit-code`.
+    fn found(&self) -> bool;
+}
+
+fn indent(output_mode: OutputMode) -> &'static str {
+    match output_mode > OutputMode::Quiet {
+        true => "    ",
+        _ => "",
+    }
+}
+
+fn merge_counts(counts: &mut [u64], other: &[u64]) {
+    for (count, other) in counts.iter_mut().zip(other) {
+        *count += other;
+    }
+}
+
+fn merge_map<K: Ord>(counts: &mut BTreeMap<K, u64>, other: BTreeMap<K, u64>) {
+    for (key, count) in other {
+        *counts.entry(key).or_default() += count;
+    }
+}
+
+/// Percentage of `count` in `total`.
+pub fn ratio(count: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        t => count as f64 / t as f64 * 100.0,
+    }
+}
+
+const MMX: &[CpuidFeature] = &[CpuidFeature::MMX];
+
+/// All generations of the x87 floating point unit.
+const X87: &[CpuidFeature] = {
+    use CpuidFeature::*;
+    &[FPU, FPU287, FPU287XL_ONLY, FPU387, FPU387SL_ONLY, CYRIX_FPU]
+};
+
+/// Legacy instruction sets, usually indicating old codegen or hand-written assembly.
+const LEGACY: &[(&str, &[CpuidFeature])] = &[("MMX", MMX), ("x87", X87)];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Legacy {
+    counts: [u64; LEGACY.len()],
+    mnemonics: [BTreeSet<Mnemonic>; LEGACY.len()],
+}
+
+impl Task for Legacy {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        let features = instruction.cpuid_features();
+        for (i, (_, group)) in LEGACY.iter().enumerate() {
+            if features.iter().any(|f| group.contains(f)) {
+                self.counts[i] += 1;
+                self.mnemonics[i].insert(instruction.mnemonic());
+            }
+        }
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Legacy instructions: ");
+        }
+
+        let indent = indent(output_mode);
+        for (((name, _), count), mnemonics) in LEGACY.iter().zip(self.counts).zip(&self.mnemonics) {
+            print!("{indent}{name}: {count} ");
+            if !mnemonics.is_empty() {
+                print!(": ");
+                for m in mnemonics {
+                    print!("{m:?} ");
+                }
+            }
+            println!();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.counts, &other.counts);
+        for (mnemonics, other) in self.mnemonics.iter_mut().zip(other.mnemonics) {
+            mnemonics.extend(other);
+        }
+    }
+
+    fn found(&self) -> bool {
+        self.counts.iter().any(|&c| c > 0)
+    }
+}
+
+/// Instructions which can only be executed in ring 0 (or with I/O privileges).
+const PRIVILEGED: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        Clgi, Cli, Clts, Hlt, In, Insb, Insd, Insw, Invd, Invept, Invlpg, Invlpga, Invpcid,
+        Invvpid, Lgdt, Lidt, Lldt, Lmsw, Ltr, Monitor, Mwait, Out, Outsb, Outsd, Outsw, Rdmsr,
+        Skinit, Stgi, Sti, Swapgs, Sysexit, Sysexitq, Sysret, Sysretq, Vmclear, Vmlaunch, Vmload,
+        Vmptrld, Vmread, Vmresume, Vmrun, Vmsave, Vmwrite, Vmxoff, Vmxon, Wbinvd, Wbnoinvd, Wrmsr,
+        Wrmsrns, Xrstors, Xsaves, Xsetbv,
+    ]
+};
+
+fn is_privileged(instruction: &Instruction) -> bool {
+    let mnemonic = instruction.mnemonic();
+    if PRIVILEGED.contains(&mnemonic) {
+        return true;
+    }
+
+    // Moves to or from control and debug registers.
+    mnemonic == Mnemonic::Mov
+        && (0..instruction.op_count()).any(|i| {
+            let r = instruction.op_register(i);
+            r.is_cr() || r.is_dr()
+        })
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Privileged {
+    mnemonics: BTreeMap<Mnemonic, u64>,
+}
+
+impl Task for Privileged {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        if is_privileged(instruction) {
+            *self.mnemonics.entry(instruction.mnemonic()).or_default() += 1;
+        }
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!(
+                "Privileged instructions: {}",
+                self.mnemonics.values().sum::<u64>()
+            );
+        }
+
+        let indent = indent(output_mode);
+        for (mnemonic, count) in &self.mnemonics {
+            println!("{indent}{mnemonic:?}: {count}");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_map(&mut self.mnemonics, other.mnemonics);
+    }
+
+    fn found(&self) -> bool {
+        !self.mnemonics.is_empty()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Cet {
+    endbr: u64,
+    calls: u64,
+    jumps: u64,
+    /// Addresses of indirect branches, potential CFI bypass points.
+    indirect: Vec<u64>,
+}
+
+impl Task for Cet {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        match instruction.mnemonic() {
+            Mnemonic::Endbr32 | Mnemonic::Endbr64 => self.endbr += 1,
+            _ => {}
+        }
+        match instruction.flow_control() {
+            FlowControl::IndirectCall => self.calls += 1,
+            FlowControl::IndirectBranch => self.jumps += 1,
+            _ => return,
+        }
+        self.indirect.push(instruction.ip());
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        match self.endbr {
+            0 => println!("CET: disabled"),
+            n => println!("CET: enabled ({n} endbr instructions)"),
+        }
+
+        if output_mode > OutputMode::Quiet {
+            let indirect = self.calls + self.jumps;
+            println!("Indirect branches: {indirect}");
+            println!("    Calls: {}", self.calls);
+            println!("    Jumps: {}", self.jumps);
+            if indirect > 0 {
+                println!(
+                    "Landing pads per indirect branch: {:.precision$}",
+                    self.endbr as f64 / indirect as f64
+                );
+            }
+        }
+
+        if output_mode > OutputMode::Normal && !self.indirect.is_empty() {
+            print!("Indirect branches at: ");
+            for address in &self.indirect {
+                print!("0x{address:x} ");
+            }
+            println!();
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.endbr += other.endbr;
+        self.calls += other.calls;
+        self.jumps += other.jumps;
+        self.indirect.extend(other.indirect);
+    }
+
+    fn found(&self) -> bool {
+        self.endbr == 0
+    }
+}
+
+/// Hardware random number generator features, relevant for entropy source audits.
+const RNG: &[CpuidFeature] = &[CpuidFeature::RDRAND, CpuidFeature::RDSEED];
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Rng {
+    counts: [u64; RNG.len()],
+}
+
+impl Task for Rng {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        let features = instruction.cpuid_features();
+        for (count, feature) in self.counts.iter_mut().zip(RNG) {
+            *count += features.contains(feature) as u64;
+        }
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Hardware RNG instructions: ");
+        }
+
+        let indent = indent(output_mode);
+        for (feature, count) in RNG.iter().zip(self.counts) {
+            println!("{indent}{feature:?}: {count}");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.counts, &other.counts);
+    }
+
+    fn found(&self) -> bool {
+        self.counts.iter().any(|&c| c > 0)
+    }
+}
+
+/// Restricted transactional memory (RTM) instructions, fault where TSX is disabled.
+const RTM: &[Mnemonic] = &[
+    Mnemonic::Xbegin,
+    Mnemonic::Xend,
+    Mnemonic::Xabort,
+    Mnemonic::Xtest,
+];
+
+/// Transactional memory (TSX) usage, disabled by microcode updates on many CPUs.
+#[derive(Debug, Default, PartialEq)]
+pub struct Tsx {
+    rtm: [u64; RTM.len()],
+    /// Hardware lock elision (HLE) prefixes.
+    xacquire: u64,
+    xrelease: u64,
+}
+
+impl Task for Tsx {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        let mnemonic = instruction.mnemonic();
+        for (count, rtm) in self.rtm.iter_mut().zip(RTM) {
+            *count += (mnemonic == *rtm) as u64;
+        }
+        self.xacquire += instruction.has_xacquire_prefix() as u64;
+        self.xrelease += instruction.has_xrelease_prefix() as u64;
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Transactional memory instructions: ");
+        }
+
+        let indent = indent(output_mode);
+        for (mnemonic, count) in RTM.iter().zip(self.rtm) {
+            println!("{indent}{mnemonic:?}: {count}");
+        }
+        println!("{indent}Xacquire: {}", self.xacquire);
+        println!("{indent}Xrelease: {}", self.xrelease);
+
+        if output_mode > OutputMode::Bare && self.rtm.iter().any(|&c| c > 0) {
+            println!("Note: RTM instructions fault on CPUs with TSX disabled, unless guarded by a CPUID check.");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.rtm, &other.rtm);
+        self.xacquire += other.xacquire;
+        self.xrelease += other.xrelease;
+    }
+
+    fn found(&self) -> bool {
+        self.rtm.iter().any(|&c| c > 0) || self.xacquire > 0 || self.xrelease > 0
+    }
+}
+
+fn uses_vector(instruction: &Instruction) -> bool {
+    (0..instruction.op_count()).any(|i| {
+        let r = instruction.op_register(i);
+        r.is_xmm() || r.is_ymm() || r.is_zmm()
+    })
+}
+
+/// Legacy SSE and VEX/EVEX encoded vector instructions, mixing them may cause transition penalties.
+#[derive(Debug, Default, PartialEq)]
+pub struct SseAvx {
+    sse: BTreeMap<Mnemonic, u64>,
+    avx: BTreeMap<Mnemonic, u64>,
+}
+
+impl Task for SseAvx {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        if !uses_vector(instruction) {
+            return;
+        }
+        let counts = match instruction.encoding() {
+            EncodingKind::Legacy => &mut self.sse,
+            EncodingKind::VEX | EncodingKind::EVEX => &mut self.avx,
+            _ => return,
+        };
+        *counts.entry(instruction.mnemonic()).or_default() += 1;
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!(
+                "Legacy SSE instructions: {}",
+                self.sse.values().sum::<u64>()
+            );
+            println!("AVX instructions: {}", self.avx.values().sum::<u64>());
+            println!("Mixed forms: ");
+        }
+
+        // AVX forms of SSE instructions are named with `v` prefix.
+        let avx: BTreeMap<_, _> = self
+            .avx
+            .iter()
+            .map(|(m, &count)| (format!("{m:?}").to_lowercase(), (m, count)))
+            .collect();
+
+        let indent = indent(output_mode);
+        for (sse, count) in &self.sse {
+            let name = format!("v{}", format!("{sse:?}").to_lowercase());
+            if let Some((avx, avx_count)) = avx.get(&name) {
+                println!("{indent}{sse:?} / {avx:?}: {count} / {avx_count}");
+            }
+        }
+
+        if output_mode > OutputMode::Bare && self.found() {
+            println!("Note: legacy SSE code mixed with AVX may cause transition penalties without vzeroupper.");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_map(&mut self.sse, other.sse);
+        merge_map(&mut self.avx, other.avx);
+    }
+
+    fn found(&self) -> bool {
+        !self.sse.is_empty() && !self.avx.is_empty()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Branches {
+    total: u64,
+    counts: BTreeMap<FlowControl, u64>,
+    /// `push rbp` followed by `mov rbp, rsp`, or 32-bit equivalent.
+    prologues: u64,
+    frame_push: bool,
+    /// Whether the first instruction completes a prologue started before, see `merge`.
+    frame_mov_first: Option<bool>,
+    /// Far calls, jumps and returns, changing the code segment.
+    far: [u64; 3],
+    /// Target registers of indirect calls and jumps.
+    targets: BTreeMap<Register, u64>,
+}
+
+/// Index of the far control transfer in `Branches::far`, if it is one.
+fn far_transfer(instruction: &Instruction) -> Option<usize> {
+    match instruction.mnemonic() {
+        _ if instruction.is_call_far() || instruction.is_call_far_indirect() => Some(0),
+        _ if instruction.is_jmp_far() || instruction.is_jmp_far_indirect() => Some(1),
+        Mnemonic::Retf => Some(2),
+        _ => None,
+    }
+}
+
+/// Register holding the target of an indirect call or jump, if not in memory.
+fn indirect_target(instruction: &Instruction) -> Option<Register> {
+    let indirect = matches!(
+        instruction.flow_control(),
+        FlowControl::IndirectCall | FlowControl::IndirectBranch
+    );
+    (indirect && instruction.op0_kind() == OpKind::Register).then(|| instruction.op0_register())
+}
+
+fn is_frame_push(instruction: &Instruction) -> bool {
+    instruction.mnemonic() == Mnemonic::Push
+        && matches!(instruction.op0_register(), Register::RBP | Register::EBP)
+}
+
+fn is_frame_mov(instruction: &Instruction) -> bool {
+    instruction.mnemonic() == Mnemonic::Mov
+        && matches!(
+            (instruction.op0_register(), instruction.op1_register()),
+            (Register::RBP, Register::RSP) | (Register::EBP, Register::ESP)
+        )
+}
+
+impl Branches {
+    /// Indirect target registers by frequency, hinting at calling conventions and dispatch.
+    fn print_targets(&self) {
+        if self.targets.is_empty() {
+            return;
+        }
+        let mut targets: Vec<_> = self.targets.iter().collect();
+        targets.sort_by_key(|&(_, &count)| Reverse(count));
+        print!("Indirect target registers:");
+        for (register, count) in targets {
+            print!(" {register:?} ({count})");
+        }
+        println!();
+    }
+}
+
+impl Task for Branches {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.total += 1;
+        *self.counts.entry(instruction.flow_control()).or_default() += 1;
+        self.frame_mov_first
+            .get_or_insert_with(|| is_frame_mov(instruction));
+        self.prologues += (self.frame_push && is_frame_mov(instruction)) as u64;
+        self.frame_push = is_frame_push(instruction);
+        if let Some(i) = far_transfer(instruction) {
+            self.far[i] += 1;
+        }
+        if let Some(register) = indirect_target(instruction) {
+            *self.targets.entry(register).or_default() += 1;
+        }
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        let indent = indent(output_mode);
+
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("Branches: ");
+        }
+
+        for flow in FlowControl::values().filter(|&f| f != FlowControl::Next) {
+            let count = self.counts.get(&flow).copied().unwrap_or(0);
+            let ratio = ratio(count, self.total);
+            println!("{indent}{flow:?}: {count} ({ratio:.precision$}%)");
+        }
+
+        if output_mode > OutputMode::Quiet {
+            let returns = self.counts.get(&FlowControl::Return).copied().unwrap_or(0);
+            println!("Approx functions: {returns}");
+            println!("Frame prologues: {}", self.prologues);
+            let [calls, jumps, returns] = self.far;
+            println!("Far transfers: {calls} calls, {jumps} jumps, {returns} returns");
+            self.print_targets();
+        }
+    }
+
+    /// A prologue may be split between the two parts.
+    fn merge(&mut self, other: Self) {
+        if other.total == 0 {
+            return;
+        }
+        let split = self.frame_push && other.frame_mov_first == Some(true);
+        self.prologues += other.prologues + split as u64;
+        self.frame_push = other.frame_push;
+        self.frame_mov_first = self.frame_mov_first.or(other.frame_mov_first);
+        self.total += other.total;
+        merge_map(&mut self.counts, other.counts);
+        merge_counts(&mut self.far, &other.far);
+        merge_map(&mut self.targets, other.targets);
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Whether the instruction uses the register explicitly, as an operand or in memory addressing.
+fn uses_register(instruction: &Instruction, register: Register) -> bool {
+    (0..instruction.op_count())
+        .map(|i| instruction.op_register(i))
+        .chain([instruction.memory_base(), instruction.memory_index()])
+        .any(|r| r != Register::None && r.full_register() == register)
+}
+
+/// Stack and frame pointer activity, relevant for unwinding.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stack {
+    total: u64,
+    rsp: u64,
+    rbp: u64,
+}
+
+impl Task for Stack {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.total += 1;
+        // `push`, `pop`, `call`, `ret` and others adjust the stack pointer implicitly.
+        self.rsp += (instruction.stack_pointer_increment() != 0
+            || uses_register(instruction, Register::RSP)) as u64;
+        self.rbp += uses_register(instruction, Register::RBP) as u64;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("Stack: ");
+        }
+
+        let indent = indent(output_mode);
+        for (name, count) in [("RSP-touching", self.rsp), ("RBP-touching", self.rbp)] {
+            let ratio = ratio(count, self.total);
+            println!("{indent}{name}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        self.rsp += other.rsp;
+        self.rbp += other.rbp;
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Coarse instruction categories, in order of precedence.
+const CATEGORIES: &[&str] = &[
+    "Control flow",
+    "SIMD",
+    "x87",
+    "System",
+    "String",
+    "Arithmetic",
+    "Logic",
+    "Data movement",
+    "Other",
+];
+
+/// Unprivileged system, fence and cache control instructions.
+/// Tables below list general purpose instructions only, vector and x87 ones are told apart
+/// by registers and features first.
+const SYSTEM: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        Cpuid,
+        Syscall,
+        Sysenter,
+        Rdtsc,
+        Rdtscp,
+        Rdpid,
+        Rdpmc,
+        Xgetbv,
+        Xsave,
+        Xsavec,
+        Xsaveopt,
+        Xsaves,
+        Xrstor,
+        Xrstors,
+        Lfence,
+        Mfence,
+        Sfence,
+        Pause,
+        Ud0,
+        Ud1,
+        Ud2,
+        Int,
+        Int1,
+        Int3,
+        Into,
+        Rdrand,
+        Rdseed,
+        Rdfsbase,
+        Rdgsbase,
+        Wrfsbase,
+        Wrgsbase,
+        Clflush,
+        Clflushopt,
+        Clwb,
+        Prefetchw,
+        Prefetchnta,
+        Prefetcht0,
+        Prefetcht1,
+        Prefetcht2,
+    ]
+};
+
+const STRING: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        Movsb, Movsw, Movsd, Movsq, Stosb, Stosw, Stosd, Stosq, Lodsb, Lodsw, Lodsd, Lodsq, Cmpsb,
+        Cmpsw, Cmpsd, Cmpsq, Scasb, Scasw, Scasd, Scasq, Insb, Insw, Insd, Outsb, Outsw, Outsd,
+    ]
+};
+
+const ARITHMETIC: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        Add, Adc, Adcx, Adox, Sub, Sbb, Mul, Mulx, Imul, Div, Idiv, Inc, Dec, Neg, Cmp, Lea, Xadd,
+        Daa, Das, Aaa, Aas, Aam, Aad,
+    ]
+};
+
+const LOGIC: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        And, Or, Xor, Not, Test, Shl, Shr, Sar, Sal, Rol, Ror, Rcl, Rcr, Shld, Shrd, Shlx, Shrx,
+        Sarx, Rorx, Bt, Bts, Btr, Btc, Bsf, Bsr, Lzcnt, Tzcnt, Popcnt, Andn, Bextr, Blsi, Blsmsk,
+        Blsr, Bzhi, Pdep, Pext,
+    ]
+};
+
+const MOVEMENT: &[Mnemonic] = {
+    use Mnemonic::*;
+    &[
+        Mov, Movzx, Movsx, Movsxd, Movbe, Movnti, Push, Pop, Pushf, Popf, Pushfq, Popfq, Xchg,
+        Cmpxchg, Cmpxchg8b, Cmpxchg16b, Bswap, Cbw, Cwde, Cdqe, Cwd, Cdq, Cqo, Lahf, Sahf, Xlatb,
+        Enter, Leave,
+    ]
+};
+
+/// Index of the instruction category in `CATEGORIES`.
+fn category(instruction: &Instruction) -> usize {
+    let mnemonic = instruction.mnemonic();
+    let features = instruction.cpuid_features();
+
+    // Traps and exceptions are not transfers of control within the program.
+    if !matches!(
+        instruction.flow_control(),
+        FlowControl::Next | FlowControl::Interrupt | FlowControl::Exception
+    ) {
+        return 0;
+    }
+    if uses_vector(instruction)
+        || features.iter().any(|f| MMX.contains(f))
+        || matches!(
+            instruction.encoding(),
+            EncodingKind::VEX | EncodingKind::EVEX | EncodingKind::XOP
+        )
+    {
+        return 1;
+    }
+    if features.iter().any(|f| X87.contains(f)) {
+        return 2;
+    }
+    if is_privileged(instruction) || SYSTEM.contains(&mnemonic) {
+        return 3;
+    }
+    if let Some(i) = [STRING, ARITHMETIC, LOGIC, MOVEMENT]
+        .iter()
+        .position(|table| table.contains(&mnemonic))
+    {
+        return 4 + i;
+    }
+
+    // Conditional moves and sets are data movement as well.
+    match instruction.condition_code() {
+        ConditionCode::None => 8,
+        _ => 7,
+    }
+}
+
+/// Instructions grouped by coarse category, the shape of the workload.
+#[derive(Debug, Default, PartialEq)]
+pub struct Categories {
+    total: u64,
+    counts: [u64; CATEGORIES.len()],
+}
+
+impl Task for Categories {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.total += 1;
+        self.counts[category(instruction)] += 1;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("Categories: ");
+        }
+
+        let indent = indent(output_mode);
+        for (name, &count) in CATEGORIES.iter().zip(&self.counts) {
+            let ratio = ratio(count, self.total);
+            println!("{indent}{name}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        merge_counts(&mut self.counts, &other.counts);
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Distribution of instructions by the number of features they belong to.
+#[derive(Debug, Default, PartialEq)]
+pub struct Overlap {
+    total: u64,
+    counts: BTreeMap<usize, u64>,
+}
+
+impl Task for Overlap {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.total += 1;
+        *self
+            .counts
+            .entry(instruction.cpuid_features().len())
+            .or_default() += 1;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("Features per instruction: ");
+        }
+
+        let indent = indent(output_mode);
+        for (&features, &count) in &self.counts {
+            let ratio = ratio(count, self.total);
+            println!("{indent}{features}: {count} ({ratio:.precision$}%)");
+        }
+
+        let multiple: u64 = self.counts.range(2..).map(|(_, &c)| c).sum();
+        if output_mode > OutputMode::Bare && multiple > 0 {
+            println!(
+                "Note: {multiple} instructions belong to multiple features, details mode counts them in each unless '--no-overlap' is used."
+            );
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        for (features, count) in other.counts {
+            *self.counts.entry(features).or_default() += count;
+        }
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Maximum length of x86 instruction.
+const MAX_LENGTH: usize = 15;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Lengths {
+    counts: [u64; MAX_LENGTH + 1],
+}
+
+impl Task for Lengths {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        if let Some(count) = self.counts.get_mut(instruction.len()) {
+            *count += 1;
+        }
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        let total: u64 = self.counts.iter().sum();
+        let bytes: u64 = (0..).zip(self.counts).map(|(l, c)| l * c).sum();
+
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {total}");
+            if total > 0 {
+                println!("Average length: {:.2}", bytes as f64 / total as f64);
+            }
+            println!("Lengths: ");
+        }
+
+        let mut lengths: Vec<_> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &c)| c > 0)
+            .collect();
+        lengths.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let indent = indent(output_mode);
+        for (length, count) in lengths {
+            let ratio = ratio(*count, total);
+            println!("{indent}{length}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.counts, &other.counts);
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Prefixes {
+    total: u64,
+    rex: u64,
+    segment: u64,
+    lock: u64,
+    rep: u64,
+    repne: u64,
+}
+
+fn is_legacy_prefix(byte: u8) -> bool {
+    matches!(
+        byte,
+        0xf0 | 0xf2 | 0xf3 | 0x2e | 0x36 | 0x3e | 0x26 | 0x64 | 0x65 | 0x66 | 0x67
+    )
+}
+
+fn has_rex_prefix(instruction: &Instruction, bytes: &[u8]) -> bool {
+    instruction.code_size() == CodeSize::Code64
+        && instruction.encoding() == EncodingKind::Legacy
+        && bytes
+            .iter()
+            .find(|&&b| !is_legacy_prefix(b))
+            .is_some_and(|b| b & 0xf0 == 0x40)
+}
+
+impl Task for Prefixes {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]) {
+        self.total += 1;
+        self.rex += has_rex_prefix(instruction, bytes) as u64;
+        self.segment += instruction.has_segment_prefix() as u64;
+        self.lock += instruction.has_lock_prefix() as u64;
+        self.rep += instruction.has_rep_prefix() as u64;
+        self.repne += instruction.has_repne_prefix() as u64;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("Prefixes: ");
+        }
+
+        let indent = indent(output_mode);
+        for (name, count) in [
+            ("REX", self.rex),
+            ("Segment", self.segment),
+            ("LOCK", self.lock),
+            ("REP", self.rep),
+            ("REPNE", self.repne),
+        ] {
+            let ratio = ratio(count, self.total);
+            println!("{indent}{name}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        self.rex += other.rex;
+        self.segment += other.segment;
+        self.lock += other.lock;
+        self.rep += other.rep;
+        self.repne += other.repne;
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Usage of every mnemonic across the whole code, regardless of features.
+#[derive(Debug, PartialEq)]
+pub struct Mnemonics {
+    counts: Vec<u64>,
+    /// Distinct mnemonic and operand count pairs, a cheap approximation of distinct encodings.
+    forms: BTreeSet<(Mnemonic, u32)>,
+}
+
+impl Default for Mnemonics {
+    fn default() -> Self {
+        Mnemonics {
+            counts: vec![0; Mnemonic::values().count()],
+            forms: BTreeSet::new(),
+        }
+    }
+}
+
+impl Task for Mnemonics {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        if let Some(count) = self.counts.get_mut(instruction.mnemonic() as usize) {
+            *count += 1;
+        }
+        self.forms
+            .insert((instruction.mnemonic(), instruction.op_count()));
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        let total: u64 = self.counts.iter().sum();
+        let mut mnemonics: Vec<_> = Mnemonic::values()
+            .zip(&self.counts)
+            .filter(|(_, &c)| c > 0)
+            .collect();
+        mnemonics.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {total}");
+            // Lower ratio means more repetitive code.
+            let forms = self.forms.len() as u64;
+            println!(
+                "Distinct forms: {forms} ({:.precision$}% of instructions)",
+                ratio(forms, total)
+            );
+            println!("Mnemonics: {}", mnemonics.len());
+        }
+
+        let indent = indent(output_mode);
+        for (mnemonic, &count) in mnemonics {
+            let ratio = ratio(count, total);
+            println!("{indent}{mnemonic:?}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.counts, &other.counts);
+        self.forms.extend(other.forms);
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Whether the instruction has a REX prefix with the W bit, a 64-bit operation.
+fn has_rex_w(instruction: &Instruction, bytes: &[u8]) -> bool {
+    has_rex_prefix(instruction, bytes)
+        && bytes
+            .iter()
+            .find(|&&b| !is_legacy_prefix(b))
+            .is_some_and(|b| b & 0x08 != 0)
+}
+
+/// Whether the instruction uses general purpose or vector registers only encodable in 64-bit mode.
+fn uses_extended(instruction: &Instruction) -> bool {
+    (0..instruction.op_count())
+        .map(|i| instruction.op_register(i))
+        .chain([instruction.memory_base(), instruction.memory_index()])
+        .any(|r| (r.is_gpr() || r.is_vector_register()) && r.number() >= 8)
+}
+
+/// Facilities of 64-bit mode, estimating the effort of porting the code to 32-bit.
+#[derive(Debug, Default, PartialEq)]
+pub struct X64 {
+    total: u64,
+    rex_w: u64,
+    extended: u64,
+    rip_relative: u64,
+    /// Instructions using any of them.
+    any: u64,
+}
+
+impl Task for X64 {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]) {
+        self.total += 1;
+        let rex_w = has_rex_w(instruction, bytes);
+        let extended = uses_extended(instruction);
+        let rip_relative = instruction.is_ip_rel_memory_operand();
+        self.rex_w += rex_w as u64;
+        self.extended += extended as u64;
+        self.rip_relative += rip_relative as u64;
+        self.any += (rex_w || extended || rip_relative) as u64;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        if output_mode > OutputMode::Quiet {
+            println!("Instructions: {}", self.total);
+            println!("64-bit only:");
+        }
+
+        let indent = indent(output_mode);
+        for (name, count) in [
+            ("REX.W", self.rex_w),
+            ("Extended registers", self.extended),
+            ("RIP-relative", self.rip_relative),
+            ("Any", self.any),
+        ] {
+            let ratio = ratio(count, self.total);
+            println!("{indent}{name}: {count} ({ratio:.precision$}%)");
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        self.rex_w += other.rex_w;
+        self.extended += other.extended;
+        self.rip_relative += other.rip_relative;
+        self.any += other.any;
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Highest psABI level required by the features used.
+#[derive(Debug, Default, PartialEq)]
+pub struct Level {
+    found: BTreeSet<CpuidFeature>,
+}
+
+impl Task for Level {
+    fn add(&mut self, instruction: &Instruction, _: &[u8]) {
+        self.found.extend(instruction.cpuid_features());
+    }
+
+    fn print(&self, output_mode: OutputMode, _: usize) {
+        if output_mode > OutputMode::Quiet {
+            print!("Level: ");
+        }
+        let found: Vec<_> = self.found.iter().copied().collect();
+        println!("{}", profiles::level(&found));
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.found.extend(other.found);
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
+
+/// Shannon entropy of code bytes along with invalid instructions, both hinting at packed code.
+#[derive(Debug, PartialEq)]
+pub struct Entropy {
+    histogram: [u64; 256],
+    total: u64,
+    invalid: u64,
+}
+
+impl Default for Entropy {
+    fn default() -> Self {
+        Entropy {
+            histogram: [0; 256],
+            total: 0,
+            invalid: 0,
+        }
+    }
+}
+
+impl Task for Entropy {
+    fn add(&mut self, instruction: &Instruction, bytes: &[u8]) {
+        for &b in bytes {
+            self.histogram[b as usize] += 1;
+        }
+        self.total += 1;
+        self.invalid += instruction.is_invalid() as u64;
+    }
+
+    fn print(&self, output_mode: OutputMode, precision: usize) {
+        let size: u64 = self.histogram.iter().sum();
+        // Float sum of nothing is negative zero.
+        let entropy = self
+            .histogram
+            .iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| {
+                let p = c as f64 / size as f64;
+                -p * p.log2()
+            })
+            .fold(0.0, |sum, e| sum + e);
+
+        if output_mode > OutputMode::Quiet {
+            print!("Entropy: ");
+        }
+        println!("{entropy:.precision$} bits per byte");
+
+        let ratio = ratio(self.invalid, self.total);
+        if output_mode > OutputMode::Quiet {
+            print!("Invalid instructions: ");
+        }
+        println!("{} ({ratio:.precision$}%)", self.invalid);
+    }
+
+    fn merge(&mut self, other: Self) {
+        merge_counts(&mut self.histogram, &other.histogram);
+        self.total += other.total;
+        self.invalid += other.invalid;
+    }
+
+    fn found(&self) -> bool {
+        false
+    }
+}
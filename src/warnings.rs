@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+
+use crate::cli::OutputMode;
+
+struct Warning {
+    message: String,
+    /// Listed in verbose mode only.
+    verbose: bool,
+}
+
+/// Warnings raised during analysis, summarized at the end.
+static WARNINGS: Mutex<Vec<Warning>> = Mutex::new(Vec::new());
+
+/// Records the warning for the summary, listed there in verbose mode only if `verbose`.
+pub fn warn(message: String, verbose: bool) {
+    if let Ok(mut warnings) = WARNINGS.lock() {
+        warnings.push(Warning { message, verbose });
+    }
+}
+
+/// Messages of all warnings, for machine readable output.
+pub fn all() -> Vec<String> {
+    WARNINGS.lock().map_or(Vec::new(), |w| {
+        w.iter().map(|w| w.message.clone()).collect()
+    })
+}
+
+pub fn count() -> usize {
+    WARNINGS.lock().map_or(0, |w| w.len())
+}
+
+/// Lists warnings shown at the output mode, as a Markdown section if `markdown`.
+pub fn print(output_mode: OutputMode, markdown: bool) {
+    let Ok(warnings) = WARNINGS.lock() else {
+        return;
+    };
+    if output_mode <= OutputMode::Bare {
+        return;
+    }
+
+    let shown: Vec<_> = warnings
+        .iter()
+        .filter(|w| !w.verbose || output_mode > OutputMode::Normal)
+        .collect();
+    if shown.is_empty() {
+        return;
+    }
+
+    match markdown {
+        true => {
+            println!("## Warnings");
+            println!();
+            for warning in shown {
+                println!("- {}", warning.message);
+            }
+        }
+        _ => {
+            println!("Warnings: {}", shown.len());
+            for warning in shown {
+                println!("    {}", warning.message);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Runs the tool over small binaries in `tests/fixtures`, built from the sources next to them.
+
+use std::{fs, process::Command};
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Output of a successful run.
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_bin-cpuflags-x86"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Exit code of a run.
+fn exit_code(args: &[&str]) -> Option<i32> {
+    Command::new(env!("CARGO_BIN_EXE_bin-cpuflags-x86"))
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .code()
+}
+
+#[test]
+fn x32_decodes_as_64_bit() {
+    let output = run(&["-v", "--summary", &fixture("x32.o")]);
+    assert!(output.contains("Architecture: X86_64_X32"));
+    assert!(output.contains("Bitness: 64"));
+    assert!(output.contains("instructions=18 features=3 invalid=0"));
+}
+
+#[test]
+fn relocatable_object_sections() {
+    let output = run(&["-v", "--list-segments", &fixture("reloc.o")]);
+    assert!(output.contains("Relocatable object, addresses are file offsets"));
+    assert!(output.contains(".text.a => 0x46 - 0x4b, offset 0x46, 5 bytes"));
+    assert!(output.contains(".text.b => 0x4b - 0x50, offset 0x4b, 5 bytes"));
+    assert!(output.contains("0x46 - 0x50, offset 0x46, 10 bytes"));
+}
+
+#[test]
+fn relocatable_object_features() {
+    let output = run(&["-q", &fixture("reloc.o")]);
+    assert_eq!(output, "AVX2\nSSE2\nX64\n");
+}
+
+#[test]
+fn ndjson_object() {
+    let output = run(&["--format", "ndjson", &fixture("reloc.o")]);
+    assert!(output
+        .trim_end()
+        .ends_with(r#""features":["AVX2","SSE2","X64"],"warnings":[]}"#));
+}
+
+#[test]
+fn ndjson_only_in_feature_modes() {
+    let path = fixture("reloc.o");
+    let ndjson = |args: &[&str]| exit_code(&[&["--format", "ndjson"], args].concat());
+    assert_eq!(ndjson(&["-m", "top", &path]), Some(7));
+    assert_eq!(ndjson(&["-D", &path, &path]), Some(7));
+}
+
+#[test]
+fn markdown_only_in_feature_modes() {
+    let path = fixture("reloc.o");
+    assert!(run(&["--format", "markdown", "-d", &path]).contains("| X64 | 2 | 50.00% |"));
+    assert_eq!(
+        exit_code(&["--format", "markdown", "-m", "lengths", &path]),
+        Some(7)
+    );
+}
+
+#[test]
+fn id_csv_always_counts() {
+    let output = run(&["--format", "id-csv", &fixture("reloc.o")]);
+    assert!(output.starts_with("id,count\n"));
+    assert_eq!(output.lines().count(), 4);
+    assert!(output.lines().skip(1).all(|l| l.split_once(',').is_some()));
+}
+
+#[test]
+fn analyze_self_without_file() {
+    assert!(run(&["--analyze-self"]).contains("Features:"));
+    assert_eq!(exit_code(&["--analyze-self", &fixture("reloc.o")]), Some(7));
+}
+
+#[test]
+fn hex_without_file() {
+    assert_eq!(run(&["-q", "--hex", "c5fdfec1"]), "AVX2\n");
+    assert_eq!(exit_code(&["--hex", "90", &fixture("reloc.o")]), Some(7));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn pid_without_other_target() {
+    for other in [
+        &["--hex", "90"][..],
+        &["--analyze-self"],
+        &[&fixture("reloc.o")],
+    ] {
+        assert_eq!(exit_code(&[&["--pid", "1"], other].concat()), Some(7));
+    }
+}
+
+#[test]
+fn warning_listed_once() {
+    let output = run(&["-b", "32", &fixture("reloc.o")]);
+    assert_eq!(output.matches("decoding as 32-bit").count(), 1);
+    assert!(output.contains("Warnings: 1\n"));
+}
+
+#[test]
+fn sampling_spans_sections() {
+    let output = run(&[
+        "-q",
+        "-m",
+        "disasm",
+        "--max-instructions",
+        "3",
+        &fixture("reloc.o"),
+    ]);
+    assert_eq!(output.lines().count(), 3);
+    assert!(output.ends_with("paddd xmm0,xmm1\n"));
+}
+
+#[test]
+fn config_file_subset_of_toml() {
+    let path = format!("{}/config.toml", env!("CARGO_TARGET_TMPDIR"));
+    let config = |text: &str| {
+        fs::write(&path, text).unwrap();
+        exit_code(&["--config", &path, &fixture("reloc.o")])
+    };
+    assert_eq!(
+        config("# Comment\nforbid = [\"SSE2\", 'AVX2'] # Inline\n"),
+        Some(6)
+    );
+    assert_eq!(config("[defaults]\nbars = true\n"), Some(7));
+    assert_eq!(config("mode = details\n"), Some(7));
+    assert_eq!(config("forbid = \"AVX512F\"\nbars = false\n"), Some(0));
+
+    let output = run(&["-v", "--config", &path, &fixture("reloc.o")]);
+    assert!(output.contains(&format!("Config: '{path}'")));
+    let both = ["--config", &path, "--no-config", &fixture("reloc.o")];
+    assert_eq!(exit_code(&both), Some(7));
+}
+
+#[test]
+fn symbol_aliases_kept() {
+    let path = fixture("alias.o");
+    for name in ["foo", "bar"] {
+        assert!(run(&["-q", "-S", name, &path]).contains("AVX2"));
+    }
+}
+
+#[test]
+fn count_beyond_baseline_by_default() {
+    let path = fixture("reloc.o");
+    assert!(run(&["-m", "count", &path]).contains("Beyond baseline: 1 (25.00%)"));
+    assert_eq!(run(&["-q", "-m", "count", &path]), "4\n");
+}
+
+#[test]
+fn level_floor_round_trips() {
+    let path = fixture("x32.o");
+    assert_eq!(run(&["-q", "-m", "level", &path]), "x86-64-v1\n");
+    assert_eq!(exit_code(&["--max-level", "x86-64-v1", &path]), Some(0));
+}
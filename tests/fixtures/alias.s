@@ -0,0 +1,11 @@
+# as alias.s -o alias.o
+        .text
+        .globl foo, bar
+        .type foo, @function
+        .type bar, @function
+foo:
+bar:
+        vpaddd %ymm1, %ymm0, %ymm0
+        ret
+        .size foo, . - foo
+        .size bar, . - bar
@@ -0,0 +1,12 @@
+# as reloc.s -o reloc.o
+        .section .text.a, "ax", @progbits
+        vpaddd %ymm1, %ymm0, %ymm0
+        ret
+
+        .section .text.b, "ax", @progbits
+        paddd %xmm1, %xmm0
+        ret
+
+        # Not code, must not be decoded.
+        .data
+        vpaddd %zmm1, %zmm0, %zmm0
@@ -0,0 +1,7 @@
+// gcc -mx32 -O2 -c x32.c -o x32.o
+long sum(long *a, long n) {
+    long s = 0;
+    for (long i = 0; i < n; i++)
+        s += a[i] * 3;
+    return s;
+}